@@ -9,6 +9,17 @@ ONE_WASM_PAGE=65536
 
 CI=${CI:-""}
 
+# Set to report how many bytes each of a project's default cargo features
+# adds to its `.wasm`.
+SIZE_REPORT=${SIZE_REPORT:-""}
+
+# Set to also build entries that have a SIMD code path with wasm SIMD
+# (`simd128`) enabled. A module that uses SIMD fails to load on hosts without
+# it, and wasm can't detect support from inside a module, so the page gets both
+# builds and `bootstrap.js` picks one when it loads.
+SIMD=${SIMD:-""}
+SIMD_RUSTFLAGS="${RUSTFLAGS:-} -C target-feature=+simd128"
+
 if test ! -d node_modules || test "$CI" != ""; then
     npm install
 fi
@@ -26,7 +37,7 @@ for x in *; do
     pushd "$x" > /dev/null
 
     # No cheating and trying to write custom stuff here.
-    rm -rf ./pkg
+    rm -rf ./pkg ./pkg-simd
 
     wasm-pack build > log.txt 2>&1 || {
         echo "Build for $x failed!"
@@ -50,6 +61,57 @@ for x in *; do
         exit 1
     fi
 
+    # Build the SIMD variant of entries that have one, after running their
+    # tests on it under node.
+    underscore_x=${x//-/_}
+    simd_module=$underscore_x
+    if test "$SIMD" != "" && grep -rq 'target_feature = "simd128"' src; then
+        simd_module=${underscore_x}_simd
+        RUSTFLAGS="$SIMD_RUSTFLAGS" cargo test --target wasm32-unknown-unknown >> log.txt 2>&1 || {
+            echo "SIMD tests for $x failed!"
+            echo "=== log ==="
+            cat log.txt
+            exit 1
+        }
+        RUSTFLAGS="$SIMD_RUSTFLAGS" wasm-pack build --out-dir pkg-simd --out-name "$simd_module" >> log.txt 2>&1 || {
+            echo "SIMD build for $x failed!"
+            echo "=== log ==="
+            cat log.txt
+            exit 1
+        }
+
+        simd_wasm_file=$(pwd)/pkg-simd/${simd_module}_bg.wasm
+        simd_js_file=$(pwd)/pkg-simd/$simd_module.js
+
+        # Only one of the two builds is loaded, so each has to fit on its own.
+        simd_wasm_size=$(wc -c "$simd_wasm_file" | awk '{ print $1 }')
+        echo "    size of SIMD wasm: $simd_wasm_size"
+        simd_js_size=$(wc -c "$simd_js_file" | awk '{ print $1 }')
+        simd_total_size=$(( $simd_js_size + $simd_wasm_size ))
+        echo "    SIMD total size: $simd_total_size"
+        if [[ "$simd_total_size" -gt "$ONE_WASM_PAGE" ]]; then
+            echo "    Project $x with SIMD is $simd_total_size bytes -- that's bigger than $ONE_WASM_PAGE!"
+            exit 1
+        fi
+    fi
+
+    # Rebuild without each default feature in turn to see what it costs.
+    if test "$SIZE_REPORT" != ""; then
+        features=$(sed -n 's/^default = \[\(.*\)\]/\1/p' Cargo.toml | tr -d '" ' | tr ',' ' ')
+        for feature in $features; do
+            others=$(echo $features | tr ' ' '\n' | grep -vx "$feature" | paste -sd, -)
+            wasm-pack build --out-dir pkg-size-report -- --no-default-features --features "$others" >> log.txt 2>&1 || {
+                echo "Build for $x without $feature failed!"
+                echo "=== log ==="
+                cat log.txt
+                exit 1
+            }
+            without_size=$(wc -c pkg-size-report/*.wasm | awk '{ print $1 }')
+            echo "    size of feature $feature: $(( $wasm_size - $without_size ))"
+            rm -rf ./pkg-size-report
+        done
+    fi
+
     # Create the webpack page that pulls in the wasm and js.
     mkdir -p "../../built/$x"
     cd "../../built/$x/" > /dev/null
@@ -59,9 +121,14 @@ for x in *; do
     cp "$ROOT/template/bootstrap.js" .
     cp "$wasm_file" .
     cp "$js_file" .
+    if test "$simd_module" != "$underscore_x"; then
+        cp "$simd_wasm_file" .
+        cp "$simd_js_file" .
+    fi
 
-    # Make the bootstrap file import the correct module.
-    underscore_x=${x//-/_}
+    # Make the bootstrap file import the correct modules. Entries without a
+    # SIMD build import the same module either way.
+    sed -i -e "s|XXX_SIMD_MODULE|$simd_module|g" bootstrap.js
     sed -i -e "s|XXX_MODULE|$underscore_x|g" bootstrap.js index.html
     sed -i -e "s|XXX_JS_SIZE|$js_size|g" bootstrap.js index.html
     sed -i -e "s|XXX_WASM_SIZE|$wasm_size|g" bootstrap.js index.html
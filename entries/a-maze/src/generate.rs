@@ -0,0 +1,548 @@
+// Ways to carve a maze, one step per frame. Each leaves a different texture
+// behind, and draws its own progress differently while it works.
+
+use super::{bar, random, set_head, set_tail, Color, Pos, TAIL_COLOR};
+use maze::{direction, Maze, DIRECTIONS};
+
+pub trait Generator: Send {
+    fn new(maze: &mut Maze) -> Self
+    where
+        Self: Sized;
+
+    // Take one step, returning false if the maze was already finished.
+    fn step(&mut self, maze: &mut Maze) -> bool;
+
+    // What to fill `coord` with, if not its usual color.
+    fn color(&self, _maze: &Maze, _coord: Pos) -> Option<Color> {
+        None
+    }
+
+    // Draw anything else, on top of the maze.
+    fn draw(&self, _frame_buffer: &mut [u8], _maze: &Maze) {}
+}
+
+type Start = fn(&mut Maze) -> Box<dyn Generator>;
+
+fn start<G: Generator + 'static>(maze: &mut Maze) -> Box<dyn Generator> {
+    Box::new(G::new(maze))
+}
+
+pub const GENERATORS: [Start; 7] = [
+    start::<Backtracker>,
+    start::<Kruskal>,
+    start::<Prim>,
+    start::<Wilson>,
+    start::<AldousBroder>,
+    start::<Eller>,
+    start::<BinaryTree>,
+];
+
+// Colors for telling apart the sets of connected cells that Kruskal's and
+// Eller's algorithms join together.
+const SET_COLORS: [Color; 8] = [
+    Color {
+        r: 231,
+        g: 191,
+        b: 122,
+    },
+    Color {
+        r: 160,
+        g: 196,
+        b: 214,
+    },
+    Color {
+        r: 214,
+        g: 160,
+        b: 170,
+    },
+    Color {
+        r: 240,
+        g: 200,
+        b: 212,
+    },
+    Color {
+        r: 196,
+        g: 178,
+        b: 220,
+    },
+    Color {
+        r: 238,
+        g: 220,
+        b: 150,
+    },
+    Color {
+        r: 150,
+        g: 204,
+        b: 180,
+    },
+    Color {
+        r: 226,
+        g: 170,
+        b: 140,
+    },
+];
+
+fn set_color(set: usize) -> Color {
+    SET_COLORS[set % SET_COLORS.len()]
+}
+
+// A random number from 0 up to but not including `n`.
+fn pick(n: usize) -> usize {
+    (random() * n as f64) as usize
+}
+
+fn shuffle<T>(items: &mut [T]) {
+    for i in 0..items.len() {
+        let j = i + pick(items.len() - i);
+        items.swap(i, j);
+    }
+}
+
+fn random_cell(maze: &Maze) -> Pos {
+    let size = maze.size() as usize;
+    Pos::new(pick(size) as isize, pick(size) as isize)
+}
+
+// Directions from `coord` that stay inside the maze.
+fn inside(maze: &Maze, coord: Pos) -> impl Iterator<Item = usize> + '_ {
+    (0..4).filter(move |&dir| maze.contains(coord + DIRECTIONS[dir]))
+}
+
+// Walk to a random unvisited neighbor, knocking down the wall in between, and
+// back up when there are none. Long, winding corridors with few dead ends.
+pub struct Backtracker {
+    // The path from the first cell to the one being visited, which is the
+    // last. Backtracking pops it.
+    stack: Vec<Pos>,
+}
+
+impl Generator for Backtracker {
+    // Start from the middle cell.
+    fn new(maze: &mut Maze) -> Backtracker {
+        let start = Pos::new(maze.size() / 2, maze.size() / 2);
+        maze.visit(start);
+        Backtracker { stack: vec![start] }
+    }
+
+    fn step(&mut self, maze: &mut Maze) -> bool {
+        let head = match self.stack.last() {
+            Some(&head) => head,
+            None => return false,
+        };
+
+        let mut directions = [0, 1, 2, 3];
+        shuffle(&mut directions);
+        for &dir in directions.iter() {
+            let next = head + DIRECTIONS[dir];
+            if maze.contains(next) && !maze.is_visited(next) {
+                maze.connect(head, dir);
+                maze.visit(next);
+                self.stack.push(next);
+                return true;
+            }
+        }
+
+        // backtrack
+        self.stack.pop();
+        true
+    }
+
+    fn draw(&self, frame_buffer: &mut [u8], _maze: &Maze) {
+        if let Some((&head, tail)) = self.stack.split_last() {
+            for &coord in tail {
+                set_tail(frame_buffer, coord);
+            }
+            set_head(frame_buffer, head);
+        }
+    }
+}
+
+// Knock down walls in a random order, skipping any between two cells that
+// are already connected. Each cell is drawn in the color of the set of cells
+// it is connected to, so the sets can be seen growing and merging.
+pub struct Kruskal {
+    // The walls below and to the right of each cell, shuffled.
+    walls: Vec<(Pos, usize)>,
+    // A union-find forest over the cells: each cell's parent, which is
+    // itself for the root of its set, and how many cells each root's set has.
+    parent: Vec<usize>,
+    count: Vec<usize>,
+}
+
+impl Kruskal {
+    fn find(&self, mut cell: usize) -> usize {
+        while self.parent[cell] != cell {
+            cell = self.parent[cell];
+        }
+        cell
+    }
+}
+
+impl Generator for Kruskal {
+    fn new(maze: &mut Maze) -> Kruskal {
+        let mut walls = Vec::new();
+        for y in 0..maze.size() {
+            for x in 0..maze.size() {
+                let coord = Pos::new(x, y);
+                for &dir in &[2, 3] {
+                    if maze.contains(coord + DIRECTIONS[dir]) {
+                        walls.push((coord, dir));
+                    }
+                }
+            }
+        }
+        shuffle(&mut walls);
+
+        let cells = (maze.size() * maze.size()) as usize;
+        Kruskal {
+            walls,
+            parent: (0..cells).collect(),
+            count: vec![1; cells],
+        }
+    }
+
+    // Knock down the next wall that joins two sets.
+    fn step(&mut self, maze: &mut Maze) -> bool {
+        while let Some((coord, dir)) = self.walls.pop() {
+            let next = coord + DIRECTIONS[dir];
+            let a = self.find(maze.index(coord));
+            let b = self.find(maze.index(next));
+            if a == b {
+                continue;
+            }
+
+            // The smaller set joins the larger one, which keeps the trees
+            // shallow.
+            let (small, large) = if self.count[a] < self.count[b] {
+                (a, b)
+            } else {
+                (b, a)
+            };
+            self.parent[small] = large;
+            self.count[large] += self.count[small];
+
+            maze.connect(coord, dir);
+            maze.visit(coord);
+            maze.visit(next);
+            return true;
+        }
+        false
+    }
+
+    // Cells that haven't been joined to any others yet keep their color.
+    fn color(&self, maze: &Maze, coord: Pos) -> Option<Color> {
+        let root = self.find(maze.index(coord));
+        if self.count[root] > 1 {
+            Some(set_color(root))
+        } else {
+            None
+        }
+    }
+}
+
+// Grow the maze from one cell by joining a random cell next to it, from
+// anywhere around its edge. Short, branching dead ends radiating out from
+// the start.
+pub struct Prim {
+    // The unvisited cells next to the maze.
+    frontier: Vec<Pos>,
+    last: Pos,
+}
+
+impl Prim {
+    fn add_neighbors(&mut self, maze: &Maze, coord: Pos) {
+        for dir in inside(maze, coord) {
+            let next = coord + DIRECTIONS[dir];
+            if !maze.is_visited(next) && !self.frontier.contains(&next) {
+                self.frontier.push(next);
+            }
+        }
+    }
+}
+
+impl Generator for Prim {
+    fn new(maze: &mut Maze) -> Prim {
+        let start = random_cell(maze);
+        maze.visit(start);
+        let mut prim = Prim {
+            frontier: Vec::new(),
+            last: start,
+        };
+        prim.add_neighbors(maze, start);
+        prim
+    }
+
+    fn step(&mut self, maze: &mut Maze) -> bool {
+        if self.frontier.is_empty() {
+            return false;
+        }
+
+        let cell = self.frontier.swap_remove(pick(self.frontier.len()));
+        let joins: Vec<usize> = inside(maze, cell)
+            .filter(|&dir| maze.is_visited(cell + DIRECTIONS[dir]))
+            .collect();
+        maze.connect(cell, joins[pick(joins.len())]);
+        maze.visit(cell);
+        self.add_neighbors(maze, cell);
+        self.last = cell;
+        true
+    }
+
+    fn draw(&self, frame_buffer: &mut [u8], _maze: &Maze) {
+        for &coord in &self.frontier {
+            set_tail(frame_buffer, coord);
+        }
+        set_head(frame_buffer, self.last);
+    }
+}
+
+// Walk randomly from a cell outside the maze until reaching it, erasing any
+// loops the walk makes along the way, and then carve the walk into the
+// maze. Every possible maze is equally likely, but the first walks take a
+// long time to find the single cell the maze starts as.
+pub struct Wilson {
+    walk: Vec<Pos>,
+}
+
+impl Generator for Wilson {
+    fn new(maze: &mut Maze) -> Wilson {
+        maze.visit(random_cell(maze));
+        Wilson { walk: Vec::new() }
+    }
+
+    fn step(&mut self, maze: &mut Maze) -> bool {
+        let head = match self.walk.last() {
+            Some(&head) => head,
+            None => {
+                let mut unvisited = Vec::new();
+                for y in 0..maze.size() {
+                    for x in 0..maze.size() {
+                        if !maze.is_visited(Pos::new(x, y)) {
+                            unvisited.push(Pos::new(x, y));
+                        }
+                    }
+                }
+                if unvisited.is_empty() {
+                    return false;
+                }
+                self.walk.push(unvisited[pick(unvisited.len())]);
+                return true;
+            }
+        };
+
+        let directions: Vec<usize> = inside(maze, head).collect();
+        let next = head + DIRECTIONS[directions[pick(directions.len())]];
+        if let Some(i) = self.walk.iter().position(|&coord| coord == next) {
+            // Erase the loop.
+            self.walk.truncate(i + 1);
+        } else if maze.is_visited(next) {
+            self.walk.push(next);
+            for pair in self.walk.windows(2) {
+                maze.connect(pair[0], direction(pair[0], pair[1]));
+                maze.visit(pair[0]);
+            }
+            self.walk.clear();
+        } else {
+            self.walk.push(next);
+        }
+        true
+    }
+
+    fn draw(&self, frame_buffer: &mut [u8], _maze: &Maze) {
+        for pair in self.walk.windows(2) {
+            bar(frame_buffer, pair[0], pair[1], TAIL_COLOR);
+        }
+        if let Some(&head) = self.walk.last() {
+            set_head(frame_buffer, head);
+        }
+    }
+}
+
+// Walk randomly all over, knocking down the wall into every cell the first
+// time the walk reaches it. Like Wilson's algorithm every maze is equally
+// likely, but here it is the last few cells that take a long time to find.
+pub struct AldousBroder {
+    walker: Pos,
+    // How many cells haven't been visited yet.
+    remaining: usize,
+}
+
+impl Generator for AldousBroder {
+    fn new(maze: &mut Maze) -> AldousBroder {
+        let walker = random_cell(maze);
+        maze.visit(walker);
+        AldousBroder {
+            walker,
+            remaining: (maze.size() * maze.size()) as usize - 1,
+        }
+    }
+
+    fn step(&mut self, maze: &mut Maze) -> bool {
+        if self.remaining == 0 {
+            return false;
+        }
+
+        let directions: Vec<usize> = inside(maze, self.walker).collect();
+        let dir = directions[pick(directions.len())];
+        let next = self.walker + DIRECTIONS[dir];
+        if !maze.is_visited(next) {
+            maze.connect(self.walker, dir);
+            maze.visit(next);
+            self.remaining -= 1;
+        }
+        self.walker = next;
+        true
+    }
+
+    fn draw(&self, frame_buffer: &mut [u8], _maze: &Maze) {
+        set_head(frame_buffer, self.walker);
+    }
+}
+
+// Carve one row at a time, keeping track of which cells in the row are
+// connected through the rows above. First randomly join neighbors in
+// different sets, then drop at least one opening from every set to the row
+// below. The last row joins every set that is left. The row being worked on
+// is drawn in the colors of its sets.
+pub struct Eller {
+    row: isize,
+    x: isize,
+    // Whether the row is being joined sideways, or dropped down from.
+    joining: bool,
+    // The set each cell in the row belongs to.
+    sets: Vec<usize>,
+    // The set each cell in the next row belongs to, for the ones that have
+    // been dropped into.
+    below: Vec<Option<usize>>,
+    next_set: usize,
+}
+
+impl Generator for Eller {
+    fn new(maze: &mut Maze) -> Eller {
+        let size = maze.size() as usize;
+        Eller {
+            row: 0,
+            x: 0,
+            joining: true,
+            sets: (0..size).collect(),
+            below: vec![None; size],
+            next_set: size,
+        }
+    }
+
+    fn step(&mut self, maze: &mut Maze) -> bool {
+        let size = maze.size();
+        if self.row == size {
+            return false;
+        }
+
+        let last_row = self.row == size - 1;
+        let coord = Pos::new(self.x, self.row);
+        let x = self.x as usize;
+
+        if self.joining {
+            maze.visit(coord);
+            if self.x + 1 < size {
+                let (a, b) = (self.sets[x], self.sets[x + 1]);
+                if a != b && (last_row || random() < 0.5) {
+                    maze.connect(coord, 3);
+                    for set in self.sets.iter_mut() {
+                        if *set == b {
+                            *set = a;
+                        }
+                    }
+                }
+                self.x += 1;
+            } else if last_row {
+                self.row += 1;
+            } else {
+                self.x = 0;
+                self.joining = false;
+            }
+            return true;
+        }
+
+        // A set has to drop down from its last cell if it hasn't yet.
+        let set = self.sets[x];
+        let dropped = self.below[..x].contains(&Some(set));
+        let last_of_set = !self.sets[x + 1..].contains(&set);
+        if random() < 0.5 || (last_of_set && !dropped) {
+            maze.connect(coord, 2);
+            self.below[x] = Some(set);
+        }
+
+        self.x += 1;
+        if self.x == size {
+            // Move down, with new sets for the cells nothing dropped into.
+            for (set, below) in self.sets.iter_mut().zip(self.below.iter_mut()) {
+                *set = match below.take() {
+                    Some(set) => set,
+                    None => {
+                        self.next_set += 1;
+                        self.next_set
+                    }
+                };
+            }
+            self.row += 1;
+            self.x = 0;
+            self.joining = true;
+        }
+        true
+    }
+
+    fn color(&self, _maze: &Maze, coord: Pos) -> Option<Color> {
+        let x = coord.x as usize;
+        if coord.y == self.row {
+            Some(set_color(self.sets[x]))
+        } else if coord.y == self.row + 1 {
+            self.below[x].map(set_color)
+        } else {
+            None
+        }
+    }
+
+    fn draw(&self, frame_buffer: &mut [u8], maze: &Maze) {
+        let head = Pos::new(self.x, self.row);
+        if maze.contains(head) {
+            set_head(frame_buffer, head);
+        }
+    }
+}
+
+// Go through the cells in order and knock down the wall above or to the left
+// of each, at random. Every cell leads up or left, so the top row and left
+// column are long, straight corridors, and everything runs diagonally.
+pub struct BinaryTree {
+    // The next cell, counting row by row.
+    next: isize,
+}
+
+impl Generator for BinaryTree {
+    fn new(_maze: &mut Maze) -> BinaryTree {
+        BinaryTree { next: 0 }
+    }
+
+    fn step(&mut self, maze: &mut Maze) -> bool {
+        let size = maze.size();
+        if self.next == size * size {
+            return false;
+        }
+
+        let coord = Pos::new(self.next % size, self.next / size);
+        let directions: Vec<usize> = inside(maze, coord).filter(|&dir| dir < 2).collect();
+        if !directions.is_empty() {
+            maze.connect(coord, directions[pick(directions.len())]);
+        }
+        maze.visit(coord);
+        self.next += 1;
+        true
+    }
+
+    fn draw(&self, frame_buffer: &mut [u8], maze: &Maze) {
+        let size = maze.size();
+        let head = Pos::new(self.next % size, self.next / size);
+        if maze.contains(head) {
+            set_head(frame_buffer, head);
+        }
+    }
+}
@@ -1,8 +1,17 @@
 extern crate cfg_if;
+extern crate lazy_static;
 extern crate wasm_bindgen;
 
+mod generate;
+mod maze;
+mod solve;
 mod utils;
+use generate::{Generator, GENERATORS};
+use lazy_static::lazy_static;
+use maze::{Maze, DIRECTIONS};
+use solve::Solver;
 use std::ops::{Add, Div, Mul};
+use std::sync::Mutex;
 use wasm_bindgen::prelude::*;
 
 #[wasm_bindgen]
@@ -15,8 +24,11 @@ const WIDTH: isize = 256;
 const HEIGHT: isize = 256;
 const GRID: isize = 15; // odd, divisible by 255
 const DOT_SIZE: isize = 10; // even, less than GRID
+const PATH_WIDTH: isize = 4; // even, less than DOT_SIZE
 const CELL_DIM: isize = WIDTH / GRID;
 
+const KEY_G: u32 = 71;
+
 const HEAD_COLOR: Color = Color {
     r: 143,
     g: 59,
@@ -42,17 +54,12 @@ const UNVISITED_COLOR: Color = Color {
     g: 141,
     b: 60,
 };
-
-const CONNECTED: Color = VISITED_COLOR;
-
-#[derive(PartialEq, Copy, Clone)]
-enum CellType {
-    OutOfBounds,
-    Head,
-    Tail,
-    Visited,
-    Unvisited,
-}
+const SEARCHED_COLOR: Color = Color {
+    r: 228,
+    g: 222,
+    b: 176,
+};
+const PATH_COLOR: Color = HEAD_COLOR;
 
 #[derive(PartialEq, Copy, Clone)]
 struct Color {
@@ -61,7 +68,7 @@ struct Color {
     b: u8,
 }
 
-#[derive(Copy, Clone)]
+#[derive(PartialEq, Copy, Clone)]
 struct Pos {
     x: isize,
     y: isize,
@@ -119,20 +126,11 @@ impl Pos {
 
 fn set_pixel(frame_buffer: &mut [u8], pos: Pos, color: Color) {
     let idx = (pos.x + pos.y * WIDTH) as usize * 4;
-    frame_buffer[idx + 0] = color.r;
+    frame_buffer[idx] = color.r;
     frame_buffer[idx + 1] = color.g;
     frame_buffer[idx + 2] = color.b;
 }
 
-fn get_pixel(frame_buffer: &[u8], pos: Pos) -> Color {
-    let idx = (pos.x + pos.y * WIDTH) as usize * 4;
-    Color {
-        r: frame_buffer[idx + 0],
-        g: frame_buffer[idx + 1],
-        b: frame_buffer[idx + 2],
-    }
-}
-
 fn full_square(frame_buffer: &mut [u8], coord: Pos, color: Color) {
     let corner = coord * GRID + 1;
 
@@ -164,7 +162,19 @@ fn set_tail(frame_buffer: &mut [u8], coord: Pos) {
     middle_square(frame_buffer, coord, TAIL_COLOR);
 }
 
-fn init_buffer(frame_buffer: &mut [u8]) {
+// Draw a line `PATH_WIDTH` wide between the middles of two neighboring cells.
+fn bar(frame_buffer: &mut [u8], from: Pos, to: Pos, color: Color) {
+    let inset = (DOT_SIZE - PATH_WIDTH) / 2;
+    let (a, b) = (head_pos(from) + inset, head_pos(to) + inset);
+    for x in a.x.min(b.x)..(a.x.max(b.x) + PATH_WIDTH) {
+        for y in a.y.min(b.y)..(a.y.max(b.y) + PATH_WIDTH) {
+            set_pixel(frame_buffer, Pos::new(x, y), color);
+        }
+    }
+}
+
+// Draw the grid lines, and fill every cell with its background color.
+fn draw_grid(frame_buffer: &mut [u8], maze: &Maze) {
     for (y, row) in frame_buffer.chunks_mut(WIDTH as usize * 4).enumerate() {
         for (x, chunk) in row.chunks_mut(4).enumerate() {
             assert!(chunk.len() == 4);
@@ -181,43 +191,14 @@ fn init_buffer(frame_buffer: &mut [u8]) {
         }
     }
 
-    let mid = CELL_DIM / 2;
-    set_head(frame_buffer, Pos::new(mid, mid));
-}
-
-fn find_head(frame_buffer: &[u8]) -> Option<Pos> {
     for y in 0..CELL_DIM {
         for x in 0..CELL_DIM {
             let coord = Pos::new(x, y);
-            if cell_type(frame_buffer, coord) == CellType::Head {
-                return Some(coord);
+            if maze.is_visited(coord) {
+                full_square(frame_buffer, coord, VISITED_COLOR);
             }
         }
     }
-
-    None
-}
-
-fn cell_type(frame_buffer: &[u8], coord: Pos) -> CellType {
-    if coord.x < 0 || coord.y < 0 || coord.x >= CELL_DIM || coord.y >= CELL_DIM {
-        return CellType::OutOfBounds;
-    }
-
-    let head_pixel = get_pixel(frame_buffer, head_pos(coord));
-    if head_pixel == HEAD_COLOR {
-        return CellType::Head;
-    }
-    if head_pixel == TAIL_COLOR {
-        return CellType::Tail;
-    }
-    if head_pixel == VISITED_COLOR {
-        return CellType::Visited;
-    }
-    if head_pixel == UNVISITED_COLOR {
-        return CellType::Unvisited;
-    }
-
-    return CellType::OutOfBounds;
 }
 
 // Find the middle coordinate between two neighboring cells
@@ -227,7 +208,9 @@ fn mid(from: Pos, to: Pos) -> Pos {
     ((from + to + 1) * GRID) / 2
 }
 
-fn connect(frame_buffer: &mut [u8], from: Pos, to: Pos) {
+// Knock down the wall between two neighboring cells, either of which may be
+// just outside the maze.
+fn connect(frame_buffer: &mut [u8], from: Pos, to: Pos, color: Color) {
     let dx = from.x - to.x;
     let dy = from.y - to.y;
     assert!((dx).abs() + (dy).abs() == 1);
@@ -235,65 +218,126 @@ fn connect(frame_buffer: &mut [u8], from: Pos, to: Pos) {
 
     let mid = mid(from, to);
     for i in 0..(GRID - 1) {
-        let pos = mid + wall_dir * (i as isize - GRID / 2 + 1);
-        set_pixel(frame_buffer, pos, CONNECTED);
+        let pos = mid + wall_dir * (i - GRID / 2 + 1);
+        set_pixel(frame_buffer, pos, color);
     }
 }
 
-fn is_connected(frame_buffer: &[u8], from: Pos, to: Pos) -> bool {
-    let mid = mid(from, to);
-    get_pixel(frame_buffer, mid) != BORDER_COLOR
+struct State {
+    maze: Maze,
+    // An index into `GENERATORS`.
+    algorithm: usize,
+    generator: Box<dyn Generator>,
+    // Once the maze is finished, the search through it.
+    solver: Option<Solver>,
 }
 
-#[wasm_bindgen]
-pub fn frame(frame_buffer: &mut [u8], key_down: bool) {
-    utils::set_panic_hook();
+impl State {
+    fn new(algorithm: usize) -> State {
+        let mut maze = Maze::new(CELL_DIM);
+        let generator = GENERATORS[algorithm](&mut maze);
+        State {
+            maze,
+            algorithm,
+            generator,
+            solver: None,
+        }
+    }
 
-    assert!(frame_buffer.len() == (WIDTH * HEIGHT * 4) as usize);
-    if frame_buffer[3] != 255 || key_down {
-        init_buffer(frame_buffer);
-    } else {
-        let head_coord = find_head(frame_buffer);
-
-        let mut directions: [Pos; 4] = [
-            Pos::new(0, -1),
-            Pos::new(-1, 0),
-            Pos::new(0, 1),
-            Pos::new(1, 0),
-        ];
-
-        if let Some(head_coord) = head_coord {
-            let mut advanced = false;
-            // shuffle
-            for i in 0..directions.len() {
-                let j = (random() * (directions.len() - i) as f64) as usize;
-                directions.swap(i, j);
-            }
+    // Carve a bit more of the maze. Once it is finished, search it for a way
+    // from a random cell on the left edge to a random cell on the right one.
+    fn step(&mut self) {
+        if let Some(solver) = &mut self.solver {
+            solver.step(&self.maze);
+        } else if !self.generator.step(&mut self.maze) {
+            let entrance = Pos::new(0, (random() * CELL_DIM as f64) as isize);
+            let exit = Pos::new(CELL_DIM - 1, (random() * CELL_DIM as f64) as isize);
+            self.solver = Some(Solver::new(&self.maze, entrance, exit));
+        }
+    }
 
-            for dir in directions.iter() {
-                let new_coord = head_coord + *dir;
-                if cell_type(frame_buffer, new_coord) == CellType::Unvisited {
-                    full_square(frame_buffer, head_coord, VISITED_COLOR);
-                    set_tail(frame_buffer, head_coord);
-                    set_head(frame_buffer, new_coord);
-                    connect(frame_buffer, head_coord, new_coord);
-                    advanced = true;
-                    break;
+    fn draw(&self, frame_buffer: &mut [u8]) {
+        draw_grid(frame_buffer, &self.maze);
+
+        // Cells can be drawn in a different color by the generator while it
+        // works, or by the search once it has reached them. An opening
+        // between two cells of the same color is drawn in that color too.
+        let color = |coord| match &self.solver {
+            Some(solver) if solver.is_reached(&self.maze, coord) => Some(SEARCHED_COLOR),
+            Some(_) => None,
+            None => self.generator.color(&self.maze, coord),
+        };
+        let opening = |from, to| match (color(from), color(to)) {
+            (Some(a), Some(b)) if a == b => a,
+            _ => VISITED_COLOR,
+        };
+
+        // Each opening is shared by two cells, so only draw the ones below and
+        // to the right of each cell.
+        for y in 0..CELL_DIM {
+            for x in 0..CELL_DIM {
+                let coord = Pos::new(x, y);
+                if let Some(color) = color(coord) {
+                    full_square(frame_buffer, coord, color);
                 }
-            }
-            if !advanced {
-                // backtrack
-                full_square(frame_buffer, head_coord, VISITED_COLOR);
-                for dir in directions.iter() {
-                    let backtrack_coord = head_coord + *dir;
-                    if cell_type(frame_buffer, backtrack_coord) == CellType::Tail
-                        && is_connected(frame_buffer, head_coord, backtrack_coord)
-                    {
-                        set_head(frame_buffer, backtrack_coord);
-                        return;
+                for &dir in &[2, 3] {
+                    let next = coord + DIRECTIONS[dir];
+                    if self.maze.is_open(coord, dir) {
+                        connect(frame_buffer, coord, next, opening(coord, next));
                     }
                 }
             }
         }
+
+        let solver = match &self.solver {
+            Some(solver) => solver,
+            None => {
+                self.generator.draw(frame_buffer, &self.maze);
+                return;
+            }
+        };
+
+        // The entrance and exit are openings in the outer wall.
+        let (entrance, exit) = (solver.entrance, solver.exit);
+        let color = opening(entrance, entrance);
+        connect(frame_buffer, entrance, entrance + DIRECTIONS[1], color);
+        let color = opening(exit, exit);
+        connect(frame_buffer, exit, exit + DIRECTIONS[3], color);
+
+        for &coord in solver.frontier() {
+            set_tail(frame_buffer, coord);
+        }
+        let path = solver.path();
+        if let Some(&first) = path.first() {
+            bar(frame_buffer, first, first, PATH_COLOR);
+        }
+        for pair in path.windows(2) {
+            bar(frame_buffer, pair[0], pair[1], PATH_COLOR);
+        }
+    }
+}
+
+lazy_static! {
+    static ref STATE: Mutex<State> = Mutex::new(State::new(0));
+}
+
+#[wasm_bindgen]
+pub fn frame(frame_buffer: &mut [u8], key_down: bool, key_code: u32) {
+    utils::set_panic_hook();
+
+    assert!(frame_buffer.len() == (WIDTH * HEIGHT * 4) as usize);
+
+    let mut state = STATE.lock().unwrap();
+    if key_down {
+        // `G` switches to the next way of generating mazes, and any other key
+        // starts a new maze the same way.
+        let mut algorithm = state.algorithm;
+        if key_code == KEY_G {
+            algorithm = (algorithm + 1) % GENERATORS.len();
+        }
+        *state = State::new(algorithm);
+    } else {
+        state.step();
     }
+    state.draw(frame_buffer);
 }
@@ -0,0 +1,77 @@
+// A square grid of cells, which walls between them have been knocked down,
+// and which cells have been visited.
+
+use super::Pos;
+
+// Up, left, down and right. The opposite of direction `d` is `(d + 2) % 4`.
+pub const DIRECTIONS: [Pos; 4] = [
+    Pos { x: 0, y: -1 },
+    Pos { x: -1, y: 0 },
+    Pos { x: 0, y: 1 },
+    Pos { x: 1, y: 0 },
+];
+
+pub fn opposite(dir: usize) -> usize {
+    (dir + 2) % 4
+}
+
+// The direction from `from` to its neighbor `to`.
+pub fn direction(from: Pos, to: Pos) -> usize {
+    DIRECTIONS.iter().position(|&d| from + d == to).unwrap()
+}
+
+pub struct Maze {
+    size: isize,
+    // For each cell, bit `d` is set when there is no wall toward
+    // `DIRECTIONS[d]`.
+    open: Vec<u8>,
+    visited: Vec<bool>,
+}
+
+impl Maze {
+    // A `size` by `size` maze with every wall standing and no cell visited.
+    pub fn new(size: isize) -> Maze {
+        let cells = (size * size) as usize;
+        Maze {
+            size,
+            open: vec![0; cells],
+            visited: vec![false; cells],
+        }
+    }
+
+    pub fn size(&self) -> isize {
+        self.size
+    }
+
+    pub fn contains(&self, coord: Pos) -> bool {
+        coord.x >= 0 && coord.y >= 0 && coord.x < self.size && coord.y < self.size
+    }
+
+    // Where `coord` is in a `Vec` with one element per cell, row by row.
+    pub fn index(&self, coord: Pos) -> usize {
+        assert!(self.contains(coord));
+        (coord.x + coord.y * self.size) as usize
+    }
+
+    pub fn is_visited(&self, coord: Pos) -> bool {
+        self.visited[self.index(coord)]
+    }
+
+    pub fn visit(&mut self, coord: Pos) {
+        let i = self.index(coord);
+        self.visited[i] = true;
+    }
+
+    // Knock down the wall between `coord` and its neighbor toward
+    // `DIRECTIONS[dir]`.
+    pub fn connect(&mut self, coord: Pos, dir: usize) {
+        let neighbor = coord + DIRECTIONS[dir];
+        let (i, j) = (self.index(coord), self.index(neighbor));
+        self.open[i] |= 1 << dir;
+        self.open[j] |= 1 << opposite(dir);
+    }
+
+    pub fn is_open(&self, coord: Pos, dir: usize) -> bool {
+        self.open[self.index(coord)] & (1 << dir) != 0
+    }
+}
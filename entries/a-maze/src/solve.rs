@@ -0,0 +1,81 @@
+// Breadth-first search from an entrance to an exit, one cell per step, and
+// then the shortest path between them, revealed one cell per step.
+
+use super::Pos;
+use maze::{Maze, DIRECTIONS};
+use std::collections::VecDeque;
+
+pub struct Solver {
+    pub entrance: Pos,
+    pub exit: Pos,
+    // Cells that have been reached but whose neighbors haven't been looked
+    // at yet, in the order they were reached.
+    frontier: VecDeque<Pos>,
+    // For each cell that has been reached, the cell it was reached from. The
+    // entrance was reached from itself.
+    came_from: Vec<Option<Pos>>,
+    // From the entrance to the exit, once the exit has been reached.
+    path: Vec<Pos>,
+    shown: usize,
+}
+
+impl Solver {
+    pub fn new(maze: &Maze, entrance: Pos, exit: Pos) -> Solver {
+        let mut came_from = vec![None; (maze.size() * maze.size()) as usize];
+        came_from[maze.index(entrance)] = Some(entrance);
+        Solver {
+            entrance,
+            exit,
+            frontier: vec![entrance].into_iter().collect(),
+            came_from,
+            path: Vec::new(),
+            shown: 0,
+        }
+    }
+
+    pub fn step(&mut self, maze: &Maze) {
+        if !self.path.is_empty() {
+            self.shown = (self.shown + 1).min(self.path.len());
+            return;
+        }
+
+        let cell = match self.frontier.pop_front() {
+            Some(cell) => cell,
+            None => return,
+        };
+
+        if cell == self.exit {
+            // Walk back to the entrance.
+            let mut cell = cell;
+            self.path.push(cell);
+            while cell != self.entrance {
+                cell = self.came_from[maze.index(cell)].unwrap();
+                self.path.push(cell);
+            }
+            self.path.reverse();
+            self.frontier.clear();
+            return;
+        }
+
+        for (dir, &offset) in DIRECTIONS.iter().enumerate() {
+            let next = cell + offset;
+            if maze.is_open(cell, dir) && self.came_from[maze.index(next)].is_none() {
+                self.came_from[maze.index(next)] = Some(cell);
+                self.frontier.push_back(next);
+            }
+        }
+    }
+
+    pub fn is_reached(&self, maze: &Maze, coord: Pos) -> bool {
+        self.came_from[maze.index(coord)].is_some()
+    }
+
+    pub fn frontier(&self) -> impl Iterator<Item = &Pos> {
+        self.frontier.iter()
+    }
+
+    // As much of the path as has been revealed so far.
+    pub fn path(&self) -> &[Pos] {
+        &self.path[..self.shown]
+    }
+}
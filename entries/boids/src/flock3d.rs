@@ -0,0 +1,266 @@
+// A flock that flies around inside a cube, projected into the frame buffer.
+//
+// The cube is as wide as the frame buffer, so the same grid that buckets the
+// 2D flock can bucket this one by `x` and `y`: any boid within `Boid::RADIUS`
+// in 3D is also within it in that plane.
+
+use super::{draw_glyph, Boid, Color, Params, COLORS, HEIGHT, WIDTH};
+use grid::Grid;
+use std::f64;
+
+// The length of the cube's sides.
+const SIZE: f64 = WIDTH as f64;
+
+// How far from a wall boids start to steer away from it.
+const WALL_MARGIN: f64 = 30.0;
+
+// The camera looks at the center of the cube from this far away.
+const CAMERA_DISTANCE: f64 = 500.0;
+const FOCAL_LENGTH: f64 = 380.0;
+
+// How far the view is tilted down, and how fast it rotates on its own, in
+// radians.
+const PITCH: f64 = 0.35;
+const AUTO_YAW: f64 = 0.003;
+
+type Vec3 = [f64; 3];
+
+fn add(a: Vec3, b: Vec3) -> Vec3 {
+    [a[0] + b[0], a[1] + b[1], a[2] + b[2]]
+}
+
+fn sub(a: Vec3, b: Vec3) -> Vec3 {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+fn scale(a: Vec3, s: f64) -> Vec3 {
+    [a[0] * s, a[1] * s, a[2] * s]
+}
+
+fn dot(a: Vec3, b: Vec3) -> f64 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn normalize(a: Vec3) -> Option<Vec3> {
+    let length = dot(a, a).sqrt();
+    if length > 0.0 {
+        Some(scale(a, 1.0 / length))
+    } else {
+        None
+    }
+}
+
+// Turn from `old` toward `desired` by no more than `max_turn` radians, and keep
+// the speed between `min_speed` and `max_speed`. The 2D flock uses this too,
+// with the third coordinate left at zero.
+pub fn limit(old: Vec3, desired: Vec3, min_speed: f64, max_speed: f64, max_turn: f64) -> Vec3 {
+    let speed = dot(desired, desired).sqrt().max(min_speed).min(max_speed);
+
+    let old_dir = normalize(old).unwrap_or([0.0, 1.0, 0.0]);
+    let want = normalize(desired).unwrap_or(old_dir);
+    let cos = dot(old_dir, want);
+
+    // Compare cosines rather than angles: between 0 and pi, a smaller angle
+    // has a larger cosine.
+    let (sin_max, cos_max) = max_turn.min(f64::consts::PI).sin_cos();
+    let dir = if cos >= cos_max {
+        want
+    } else {
+        // Rotate `old_dir` toward `want`, in the plane that they span. When
+        // they point in opposite directions, any perpendicular will do.
+        let perpendicular = normalize(sub(want, scale(old_dir, cos)))
+            .or_else(|| normalize([-old_dir[1], old_dir[0], 0.0]))
+            .unwrap_or([1.0, 0.0, 0.0]);
+        add(scale(old_dir, cos_max), scale(perpendicular, sin_max))
+    };
+
+    scale(dir, speed)
+}
+
+#[derive(Copy, Clone)]
+struct Boid3d {
+    position: Vec3,
+    velocity: Vec3,
+    species: usize,
+}
+
+impl Boid3d {
+    fn next(&self, me: usize, flock: &[Boid3d], grid: &Grid, params: &Params) -> Boid3d {
+        let mut num_near = 0_u32;
+        let mut sum_vel = [0.0; 3];
+        let mut sum_delta = [0.0; 3];
+        let mut separation = [0.0; 3];
+        let mut other_species = [0.0; 3];
+
+        let position_2d = [self.position[0], self.position[1]];
+        for b in grid.near(position_2d).filter(|i| *i != me).map(|i| &flock[i]) {
+            let delta = sub(b.position, self.position);
+            let dist_sq = dot(delta, delta);
+            if dist_sq > Boid::RADIUS * Boid::RADIUS {
+                continue;
+            }
+
+            if b.species != self.species {
+                if dist_sq > 0.0 {
+                    other_species = sub(other_species, scale(delta, 1.0 / dist_sq));
+                }
+                continue;
+            }
+
+            num_near += 1;
+            sum_vel = add(sum_vel, b.velocity);
+            sum_delta = add(sum_delta, delta);
+
+            let separation_radius = Boid::SEPARATION_RADIUS;
+            if dist_sq > 0.0 && dist_sq < separation_radius * separation_radius {
+                separation = sub(separation, scale(delta, 1.0 / dist_sq));
+            }
+        }
+
+        let mut steer = scale(separation, params.separation);
+        steer = add(steer, scale(other_species, params.species_avoid));
+        if num_near > 0 {
+            let n = num_near as f64;
+            let alignment = sub(scale(sum_vel, 1.0 / n), self.velocity);
+            let cohesion = scale(sum_delta, 1.0 / n);
+            steer = add(steer, scale(alignment, params.alignment));
+            steer = add(steer, scale(cohesion, params.cohesion));
+        }
+
+        // Steer away from the walls of the cube, harder the closer we are.
+        for axis in 0..3 {
+            let p = self.position[axis];
+            if p < WALL_MARGIN {
+                steer[axis] += params.avoid * (WALL_MARGIN - p) / WALL_MARGIN;
+            } else if p > SIZE - WALL_MARGIN {
+                steer[axis] -= params.avoid * (p - (SIZE - WALL_MARGIN)) / WALL_MARGIN;
+            }
+        }
+
+        let velocity = limit(
+            self.velocity,
+            add(self.velocity, steer),
+            params.min_speed,
+            params.max_speed,
+            params.max_turn,
+        );
+
+        // Like obstacles in 2D, a boid that can't turn away in time is stopped
+        // at the wall.
+        let mut position = add(self.position, velocity);
+        for p in position.iter_mut() {
+            *p = p.clamp(0.0, SIZE - 1.0);
+        }
+
+        Boid3d {
+            position,
+            velocity,
+            species: self.species,
+        }
+    }
+}
+
+pub struct Flock3d {
+    boids: Vec<Boid3d>,
+    // The view's rotation around the cube's vertical axis.
+    yaw: f64,
+}
+
+impl Flock3d {
+    // Lift a 2D flock into the cube, spreading it out in depth.
+    pub fn new(flock: &[Boid]) -> Flock3d {
+        let boids = flock
+            .iter()
+            .enumerate()
+            .map(|(i, b)| Boid3d {
+                position: [
+                    b.position[0],
+                    b.position[1],
+                    WALL_MARGIN + (i as f64 * 37.0) % (SIZE - 2.0 * WALL_MARGIN),
+                ],
+                velocity: [b.velocity[0], b.velocity[1], 0.0],
+                species: b.species,
+            }).collect();
+        Flock3d { boids, yaw: 0.0 }
+    }
+
+    pub fn add(&mut self, num_species: usize) {
+        let species = self.boids.len() % num_species;
+        self.boids.push(Boid3d {
+            position: [SIZE / 2.0; 3],
+            velocity: [0.0, 0.0, 1.0],
+            species,
+        });
+    }
+
+    pub fn turn(&mut self, by: f64) {
+        self.yaw += by;
+    }
+
+    pub fn step(&mut self, grid: &mut Grid, params: &Params) {
+        grid.rebuild(self.boids.iter().map(|b| [b.position[0], b.position[1]]));
+        let mut boids: Vec<_> = self
+            .boids
+            .iter()
+            .enumerate()
+            .map(|(i, b)| b.next(i, &self.boids, grid, params))
+            .collect();
+        self.yaw += AUTO_YAW;
+
+        // Keep the boids ordered from back to front, so that `draw` draws
+        // nearer boids over farther ones. The order barely changes from one
+        // frame to the next, so an insertion sort has little to do.
+        let depth = |b: &Boid3d| self.project(b.position).2;
+        for i in 1..boids.len() {
+            let mut j = i;
+            while j > 0 && depth(&boids[j - 1]).total_cmp(&depth(&boids[j])).is_lt() {
+                boids.swap(j - 1, j);
+                j -= 1;
+            }
+        }
+        self.boids = boids;
+    }
+
+    // Project a point in the cube to a position in the frame buffer, how much
+    // things at that point are scaled by perspective, and its depth.
+    fn project(&self, p: Vec3) -> ([f64; 2], f64, f64) {
+        let (sin_yaw, cos_yaw) = self.yaw.sin_cos();
+        let (sin_pitch, cos_pitch) = PITCH.sin_cos();
+
+        let c = sub(p, [SIZE / 2.0; 3]);
+        let x = c[0] * cos_yaw - c[2] * sin_yaw;
+        let z = c[0] * sin_yaw + c[2] * cos_yaw;
+        let y = c[1] * cos_pitch - z * sin_pitch;
+        let z = c[1] * sin_pitch + z * cos_pitch;
+
+        let s = FOCAL_LENGTH / (CAMERA_DISTANCE + z);
+        (
+            [WIDTH as f64 / 2.0 + x * s, HEIGHT as f64 / 2.0 + y * s],
+            s,
+            z,
+        )
+    }
+
+    pub fn draw(&self, buf: &mut [u8]) {
+        // The farthest any point in the cube can be from its center.
+        let extent = SIZE * 3.0_f64.sqrt() / 2.0;
+
+        for b in &self.boids {
+            let (position, s, depth) = self.project(b.position);
+            let (ahead, _, _) = self.project(add(b.position, b.velocity));
+            let heading = [ahead[0] - position[0], ahead[1] - position[1]];
+
+            // Farther boids are smaller, and faded toward the white background.
+            let fade = ((depth + extent) / (2.0 * extent)).clamp(0.0, 1.0) * 0.75;
+            let c = COLORS[b.species];
+            let fade_channel = |x: u8| (x as f64 + (255.0 - x as f64) * fade) as u8;
+            let color = Color {
+                r: fade_channel(c.r),
+                g: fade_channel(c.g),
+                b: fade_channel(c.b),
+                a: c.a,
+            };
+            draw_glyph(buf, position, heading, s * 1.25, color, false);
+        }
+    }
+}
@@ -0,0 +1,90 @@
+// A uniform grid over the world that buckets boids by position, so that
+// finding a boid's neighbors only has to look at the boids in nearby cells
+// instead of the whole flock.
+
+use super::{Boid, HEIGHT, WIDTH};
+use std::slice;
+
+// Cells are at least as big as a boid's neighborhood, so all of a boid's
+// neighbors are in its own cell or one of the eight cells around it. They also
+// evenly divide the world, so that the grid wraps around at the edges just like
+// the world does.
+const COLUMNS: usize = WIDTH / Boid::RADIUS as usize;
+const ROWS: usize = HEIGHT / Boid::RADIUS as usize;
+const CELL_WIDTH: f64 = WIDTH as f64 / COLUMNS as f64;
+const CELL_HEIGHT: f64 = HEIGHT as f64 / ROWS as f64;
+
+pub struct Grid {
+    // The indices of the boids in each cell, in row-major order.
+    cells: Vec<Vec<usize>>,
+}
+
+impl Grid {
+    pub fn new() -> Grid {
+        Grid {
+            cells: vec![vec![]; COLUMNS * ROWS],
+        }
+    }
+
+    fn cell(position: [f64; 2]) -> (usize, usize) {
+        let column = (position[0] / CELL_WIDTH) as usize % COLUMNS;
+        let row = (position[1] / CELL_HEIGHT) as usize % ROWS;
+        (column, row)
+    }
+
+    // Re-bucket every boid, given their positions in order. The cells keep
+    // their allocations between frames.
+    pub fn rebuild<I: Iterator<Item = [f64; 2]>>(&mut self, positions: I) {
+        for cell in self.cells.iter_mut() {
+            cell.clear();
+        }
+        for (i, position) in positions.enumerate() {
+            let (column, row) = Self::cell(position);
+            self.cells[column + row * COLUMNS].push(i);
+        }
+    }
+
+    // The indices of every boid that could be within `Boid::RADIUS` of
+    // `position`, including across the edges of the world.
+    pub fn near(&self, position: [f64; 2]) -> Near<'_> {
+        let (column, row) = Self::cell(position);
+        let mut cells = [0; 9];
+        for (i, cell) in cells.iter_mut().enumerate() {
+            let dc = (i % 3 + COLUMNS - 1) % COLUMNS;
+            let dr = (i / 3 + ROWS - 1) % ROWS;
+            *cell = (column + dc) % COLUMNS + (row + dr) % ROWS * COLUMNS;
+        }
+        Near {
+            grid: self,
+            cells,
+            next_cell: 0,
+            boids: [].iter(),
+        }
+    }
+}
+
+// The boids in the nine cells around a position, as returned by `Grid::near`.
+// This is a plain struct rather than a chain of iterator adapters, which
+// would be compiled again for every caller.
+pub struct Near<'a> {
+    grid: &'a Grid,
+    cells: [usize; 9],
+    next_cell: usize,
+    // The rest of the boids in the current cell.
+    boids: slice::Iter<'a, usize>,
+}
+
+impl<'a> Iterator for Near<'a> {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<usize> {
+        loop {
+            if let Some(&i) = self.boids.next() {
+                return Some(i);
+            }
+            let &cell = self.cells.get(self.next_cell)?;
+            self.boids = self.grid.cells[cell].iter();
+            self.next_cell += 1;
+        }
+    }
+}
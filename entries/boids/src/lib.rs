@@ -2,17 +2,63 @@ extern crate cfg_if;
 extern crate lazy_static;
 extern crate wasm_bindgen;
 
+mod flock3d;
+mod grid;
+mod metrics;
 mod utils;
 
+#[cfg(test)]
+mod tests;
+
+use flock3d::Flock3d;
+use grid::Grid;
 use lazy_static::lazy_static;
 use std::f64;
 use std::sync::Mutex;
 use wasm_bindgen::prelude::*;
 
 const NUM_BOIDS: usize = 25;
+const NUM_PREDATORS: usize = 1;
+const NUM_SPECIES: usize = 3;
+
+const KEY_LEFT: u32 = 37;
+const KEY_RIGHT: u32 = 39;
+const KEY_D: u32 = 68;
+const KEY_M: u32 = 77;
+const KEY_P: u32 = 80;
+
+// Static circles that boids steer around, as `(center, radius)`.
+const OBSTACLES: &[([f64; 2], f64)] = &[
+    ([70.0, 70.0], 18.0),
+    ([190.0, 110.0], 24.0),
+    ([100.0, 195.0], 14.0),
+];
+
+const OBSTACLE_COLOR: Color = Color {
+    r: 120,
+    g: 120,
+    b: 130,
+    a: 255,
+};
+
+// Where the host's pointer is over the canvas. It attracts nearby boids, and
+// repels them while it is pressed.
+#[derive(Copy, Clone)]
+struct Pointer {
+    position: [f64; 2],
+    down: bool,
+}
 
 struct State {
     flock: Vec<Boid>,
+    predators: Vec<Predator>,
+    grid: Grid,
+    params: Params,
+    // While in 3D mode, this is the flock instead of `flock`.
+    flock3d: Option<Flock3d>,
+    // Whether to draw the flock's statistics over it.
+    #[cfg(feature = "stats-overlay")]
+    show_stats: bool,
     first_frame: bool,
 }
 
@@ -25,117 +71,342 @@ lazy_static! {
                 let y = y as f64;
                 Boid {
                     position: [x * 10.0 % WIDTH as f64, (y * 25.0 + 50.0) % HEIGHT as f64],
-                    direction: x * y,
-                    color: COLORS[x as usize % COLORS.len()],
+                    velocity: [(x * y).sin(), (x * y).cos()],
+                    species: x as usize % NUM_SPECIES,
                 }
             }).collect(),
+        predators: (0..NUM_PREDATORS).map(Predator::new).collect(),
+        grid: Grid::new(),
+        params: Params::DEFAULT,
+        flock3d: None,
+        #[cfg(feature = "stats-overlay")]
+        show_stats: false,
         first_frame: true,
     });
 }
 
+// How strongly each steering force pulls on a boid, and how fast and how
+// sharply boids and predators may fly. The host can change these at any time
+// with `set_weights`, `set_limits` and `set_predators`.
+#[derive(Copy, Clone)]
+struct Params {
+    separation: f64,
+    alignment: f64,
+    cohesion: f64,
+    flee: f64,
+    avoid: f64,
+    pointer: f64,
+    min_speed: f64,
+    max_speed: f64,
+    // In radians per frame.
+    max_turn: f64,
+    predator_speed: f64,
+    // Between 1 and `COLORS.len()`.
+    num_species: usize,
+    // How strongly boids steer away from boids of other species.
+    species_avoid: f64,
+}
+
+impl Params {
+    const DEFAULT: Params = Params {
+        separation: 1.5,
+        alignment: 0.05,
+        cohesion: 0.005,
+        flee: 1.0,
+        avoid: 1.0,
+        pointer: 0.5,
+        min_speed: 0.5,
+        max_speed: 1.5,
+        max_turn: 0.1,
+        predator_speed: 1.6,
+        num_species: NUM_SPECIES,
+        species_avoid: 0.5,
+    };
+}
+
 #[derive(Copy, Clone)]
 struct Boid {
     position: [f64; 2],
-    direction: f64,
-    color: Color,
+    velocity: [f64; 2],
+    // Boids only flock with their own species, and are colored by it.
+    species: usize,
+}
+
+fn add(a: [f64; 2], b: [f64; 2]) -> [f64; 2] {
+    [a[0] + b[0], a[1] + b[1]]
+}
+
+fn sub(a: [f64; 2], b: [f64; 2]) -> [f64; 2] {
+    [a[0] - b[0], a[1] - b[1]]
+}
+
+fn scale(a: [f64; 2], s: f64) -> [f64; 2] {
+    [a[0] * s, a[1] * s]
+}
+
+// The shortest vector from `from` to `to`, taking into account that the world
+// wraps around at its edges.
+fn wrapped_delta(from: [f64; 2], to: [f64; 2]) -> [f64; 2] {
+    let wrap = |d: f64, size: f64| {
+        if d > size / 2.0 {
+            d - size
+        } else if d < -size / 2.0 {
+            d + size
+        } else {
+            d
+        }
+    };
+    [
+        wrap(to[0] - from[0], WIDTH as f64),
+        wrap(to[1] - from[1], HEIGHT as f64),
+    ]
+}
+
+fn length_squared(a: [f64; 2]) -> f64 {
+    a[0] * a[0] + a[1] * a[1]
+}
+
+// Wrap a position back into the world.
+fn wrap(a: [f64; 2]) -> [f64; 2] {
+    [
+        (a[0] + WIDTH as f64) % WIDTH as f64,
+        (a[1] + HEIGHT as f64) % HEIGHT as f64,
+    ]
+}
+
+// Turn from `old` toward `desired` by no more than `max_turn` radians, and keep
+// the speed between `min_speed` and `max_speed`.
+fn limit(
+    old: [f64; 2],
+    desired: [f64; 2],
+    min_speed: f64,
+    max_speed: f64,
+    max_turn: f64,
+) -> [f64; 2] {
+    let velocity = flock3d::limit(
+        [old[0], old[1], 0.0],
+        [desired[0], desired[1], 0.0],
+        min_speed,
+        max_speed,
+        max_turn,
+    );
+    [velocity[0], velocity[1]]
 }
 
 impl Boid {
     const RADIUS: f64 = 30.0;
 
+    // Boids closer than this are pushed apart.
+    const SEPARATION_RADIUS: f64 = 8.0;
+
+    // Boids flee from predators closer than this.
+    const FLEE_RADIUS: f64 = 40.0;
+
+    // How far from an obstacle's edge boids start to steer around it.
+    const AVOID_MARGIN: f64 = 15.0;
+
+    // How far away the pointer can attract or repel boids from.
+    const POINTER_RADIUS: f64 = 60.0;
+
     fn draw(&self, buf: &mut [u8]) {
-        set_pixel(
+        draw_glyph(
             buf,
-            self.position[0] as usize,
-            self.position[1] as usize,
-            self.color,
+            self.position,
+            self.velocity,
+            1.0,
+            COLORS[self.species],
+            true,
         );
     }
 
-    fn next(&self, me: usize, flock: &[Boid]) -> Boid {
-        let mut closest = None;
-
-        let mut sum_dir = self.direction;
-        let mut sum_pos = self.position;
-        let mut num_near = 1_u32;
-
-        flock
-            .iter()
-            .enumerate()
-            .filter(|(i, _)| *i != me)
-            .for_each(|(_, b)| {
-                let b_dist_sq = self.distance_squared(b);
-
-                if b_dist_sq <= Self::RADIUS * Self::RADIUS {
-                    num_near += 1;
-                    sum_dir += b.direction;
-                    sum_pos[0] += b.position[0];
-                    sum_pos[1] += b.position[1];
+    fn next(
+        &self,
+        me: usize,
+        flock: &[Boid],
+        grid: &Grid,
+        predators: &[Predator],
+        pointer: Option<Pointer>,
+        params: &Params,
+    ) -> Boid {
+        let mut num_near = 0_u32;
+        let mut sum_vel = [0.0, 0.0];
+        // Relative to our position, so that neighbors across an edge of the
+        // world are averaged where they appear to be from here.
+        let mut sum_delta = [0.0, 0.0];
+        let mut separation = [0.0, 0.0];
+        let mut other_species = [0.0, 0.0];
+
+        for b in grid.near(self.position).filter(|i| *i != me).map(|i| &flock[i]) {
+            let delta = wrapped_delta(self.position, b.position);
+            let dist_sq = length_squared(delta);
+            if dist_sq > Self::RADIUS * Self::RADIUS {
+                continue;
+            }
+
+            // Keep away from every boid of another species in our
+            // neighborhood, harder the closer they are.
+            if b.species != self.species {
+                if dist_sq > 0.0 {
+                    other_species = sub(other_species, scale(delta, 1.0 / dist_sq));
                 }
+                continue;
+            }
 
-                if b_dist_sq > 50.0 {
-                    return;
-                }
-
-                closest = Some(closest.map_or(b, |c| {
-                    if b_dist_sq < self.distance_squared(c) {
-                        b
-                    } else {
-                        c
-                    }
-                }));
-            });
+            num_near += 1;
+            sum_vel = add(sum_vel, b.velocity);
+            sum_delta = add(sum_delta, delta);
 
-        let avg_pos = [sum_pos[0] / num_near as f64, sum_pos[1] / num_near as f64];
-        let avg_dir = sum_dir / num_near as f64;
+            // Push away from close boids, harder the closer they are.
+            if dist_sq > 0.0 && dist_sq < Self::SEPARATION_RADIUS * Self::SEPARATION_RADIUS {
+                separation = sub(separation, scale(delta, 1.0 / dist_sq));
+            }
+        }
 
-        let mut next = self.clone();
+        let mut steer = scale(separation, params.separation);
+        steer = add(steer, scale(other_species, params.species_avoid));
+        if num_near > 0 {
+            let n = num_near as f64;
+            // Match the neighbors' average velocity.
+            let alignment = sub(scale(sum_vel, 1.0 / n), self.velocity);
+            // Move toward the neighbors' center.
+            let cohesion = scale(sum_delta, 1.0 / n);
+            steer = add(steer, scale(alignment, params.alignment));
+            steer = add(steer, scale(cohesion, params.cohesion));
+        }
 
-        let velocity = [self.direction.sin(), self.direction.cos()];
-        let left = [-velocity[1], velocity[0]];
+        // Scatter away from nearby predators.
+        for p in predators {
+            let delta = wrapped_delta(self.position, p.position);
+            let dist_sq = length_squared(delta);
+            if dist_sq > 0.0 && dist_sq < Self::FLEE_RADIUS * Self::FLEE_RADIUS {
+                steer = sub(steer, scale(delta, params.flee / dist_sq.sqrt()));
+            }
+        }
 
-        if num_near > 1 {
-            let delta = [avg_dir.sin(), avg_dir.cos()];
-            let left_right = left[0] * delta[0] + left[1] * delta[1];
-            next.direction = if left_right > 0.0 {
-                next.direction - 0.25 * left_right
-            } else {
-                next.direction - 0.25 * left_right
-            };
+        // Steer around obstacles, harder the closer we are to their edge.
+        for &(center, radius) in OBSTACLES {
+            let away = wrapped_delta(center, self.position);
+            let dist = length_squared(away).sqrt();
+            if dist > 0.0 && dist < radius + Self::AVOID_MARGIN {
+                let closeness = (radius + Self::AVOID_MARGIN - dist) / Self::AVOID_MARGIN;
+                steer = add(steer, scale(away, params.avoid * closeness / dist));
+            }
+        }
 
-            let delta = [avg_pos[0] - self.position[0], avg_pos[1] - self.position[1]];
-            let left_right = left[0] * delta[0] + left[1] * delta[1];
-            next.direction = if left_right > 0.0 {
-                next.direction - 0.02 * left_right
-            } else {
-                next.direction - 0.02 * left_right
-            };
+        if let Some(pointer) = pointer {
+            let delta = wrapped_delta(self.position, pointer.position);
+            let dist = length_squared(delta).sqrt();
+            if dist > 0.0 && dist < Self::POINTER_RADIUS {
+                let weight = if pointer.down {
+                    -params.pointer
+                } else {
+                    params.pointer
+                };
+                steer = add(steer, scale(delta, weight / dist));
+            }
         }
 
-        if let Some(closest) = closest {
-            let delta = [
-                closest.position[0] - self.position[0],
-                closest.position[1] - self.position[1],
-            ];
-            let left_right = left[0] * delta[0] + left[1] * delta[1];
-            next.direction = if left_right > 0.0 {
-                next.direction + 0.2
-            } else {
-                next.direction - 0.2
-            };
+        let velocity = limit(
+            self.velocity,
+            add(self.velocity, steer),
+            params.min_speed,
+            params.max_speed,
+            params.max_turn,
+        );
+
+        let mut next = *self;
+        next.velocity = velocity;
+        next.position = wrap(add(self.position, velocity));
+
+        // Steering is limited in how sharply it can turn, so a boid may still
+        // end up inside an obstacle. Push it back out to the edge.
+        for &(center, radius) in OBSTACLES {
+            let away = wrapped_delta(center, next.position);
+            let dist = length_squared(away).sqrt();
+            if dist > 0.0 && dist < radius {
+                next.position = wrap(add(center, scale(away, radius / dist)));
+            }
         }
 
-        next.position = [
-            (self.position[0] + velocity[0] + WIDTH as f64) % WIDTH as f64,
-            (self.position[1] + velocity[1] + HEIGHT as f64) % HEIGHT as f64,
-        ];
         next
     }
+}
+
+// Hunts the boid nearest to it. Boids that it gets close enough to are caught,
+// and respawn on the other side of the world.
+#[derive(Copy, Clone)]
+struct Predator {
+    position: [f64; 2],
+    velocity: [f64; 2],
+}
+
+impl Predator {
+    // Less agile than a boid, so that boids can dodge it.
+    const MAX_TURN: f64 = 0.06;
+
+    const CATCH_RADIUS: f64 = 3.0;
+
+    const COLOR: Color = Color {
+        r: 20,
+        g: 20,
+        b: 20,
+        a: 255,
+    };
+
+    fn new(i: usize) -> Predator {
+        let i = i as f64;
+        Predator {
+            position: wrap([i * 97.0 + 30.0, i * 61.0 + 200.0]),
+            velocity: [i.cos(), i.sin()],
+        }
+    }
 
-    fn distance_squared(&self, b: &Boid) -> f64 {
-        let dx = self.position[0] - b.position[0];
-        let dy = self.position[1] - b.position[1];
-        dx * dx + dy * dy
+    // Predators are drawn as a 3x3 block, so that they stand out from the
+    // boids.
+    fn draw(&self, buf: &mut [u8]) {
+        for dy in -1..2 {
+            for dx in -1..2 {
+                let p = wrap(add(self.position, [dx as f64, dy as f64]));
+                set_pixel(buf, p[0] as usize, p[1] as usize, Self::COLOR);
+            }
+        }
+    }
+
+    fn next(&self, flock: &[Boid], grid: &Grid, params: &Params) -> Predator {
+        let nearest = |candidates: &mut dyn Iterator<Item = usize>| {
+            candidates
+                .map(|i| wrapped_delta(self.position, flock[i].position))
+                .min_by(|a, b| length_squared(*a).total_cmp(&length_squared(*b)))
+        };
+        // Any boid within `Boid::RADIUS` is in the grid's nearby cells, so
+        // only look at the whole flock when there is none that close.
+        let desired = nearest(&mut grid.near(self.position))
+            .filter(|d| length_squared(*d) <= Boid::RADIUS * Boid::RADIUS)
+            .or_else(|| nearest(&mut (0..flock.len())))
+            .unwrap_or(self.velocity);
+        let velocity = limit(
+            self.velocity,
+            desired,
+            params.predator_speed,
+            params.predator_speed,
+            Self::MAX_TURN,
+        );
+
+        Predator {
+            position: wrap(add(self.position, velocity)),
+            velocity,
+        }
+    }
+
+    // Respawn any boids that this predator has caught.
+    fn catch(&self, flock: &mut [Boid]) {
+        let opposite = wrap(add(self.position, [WIDTH as f64 / 2.0, HEIGHT as f64 / 2.0]));
+        for b in flock.iter_mut() {
+            let delta = wrapped_delta(self.position, b.position);
+            if length_squared(delta) < Self::CATCH_RADIUS * Self::CATCH_RADIUS {
+                b.position = opposite;
+            }
+        }
     }
 }
 
@@ -214,23 +485,124 @@ const WIDTH: usize = 256;
 const HEIGHT: usize = 256;
 
 fn set_pixel(buf: &mut [u8], x: usize, y: usize, color: Color) {
-    assert!(buf.len() as usize == WIDTH * HEIGHT * 4);
+    assert!(buf.len() == WIDTH * HEIGHT * 4);
     if x >= WIDTH || y >= HEIGHT {
         return;
     }
     let a = (color.a as f64) / 255.0;
     let idx = x * 4 + y * WIDTH * 4;
-    buf[idx + 0] = (buf[idx + 0] as f64 * (1.0 - a) + color.r as f64 * a).ceil() as u8;
+    buf[idx] = (buf[idx] as f64 * (1.0 - a) + color.r as f64 * a).ceil() as u8;
     buf[idx + 1] = (buf[idx + 1] as f64 * (1.0 - a) + color.g as f64 * a).ceil() as u8;
     buf[idx + 2] = (buf[idx + 2] as f64 * (1.0 - a) + color.b as f64 * a).ceil() as u8;
     buf[idx + 3] = 255;
 }
 
+// Draw a boid as a small triangle at `position` pointing along `heading`,
+// scaled by `size`.
+fn draw_glyph(
+    buf: &mut [u8],
+    position: [f64; 2],
+    heading: [f64; 2],
+    size: f64,
+    color: Color,
+    wraps: bool,
+) {
+    let length = length_squared(heading).sqrt();
+    let forward = if length > 0.0 {
+        scale(heading, size / length)
+    } else {
+        [0.0, size]
+    };
+    let side = [-forward[1], forward[0]];
+    let back = sub(position, scale(forward, 2.0));
+    draw_triangle(
+        buf,
+        [
+            add(position, scale(forward, 4.0)),
+            add(back, scale(side, 2.5)),
+            sub(back, scale(side, 2.5)),
+        ],
+        color,
+        wraps,
+    );
+}
+
+// Draw a filled triangle, with edges anti-aliased by blending in partially
+// covered pixels. Parts that are off the edge of the frame buffer either wrap
+// around to the other side or are clipped, depending on `wraps`.
+fn draw_triangle(buf: &mut [u8], vertices: [[f64; 2]; 3], color: Color, wraps: bool) {
+    let [a, b, c] = vertices;
+
+    // Wind the triangle counter-clockwise, so that points inside it are on the
+    // positive side of every edge.
+    let area = (b[0] - a[0]) * (c[1] - a[1]) - (b[1] - a[1]) * (c[0] - a[0]);
+    let (b, c) = if area < 0.0 { (c, b) } else { (b, c) };
+    let edge_distance = |p: [f64; 2], from: [f64; 2], to: [f64; 2]| {
+        let edge = sub(to, from);
+        let rel = sub(p, from);
+        (edge[0] * rel[1] - edge[1] * rel[0]) / length_squared(edge).sqrt()
+    };
+
+    let min_x = a[0].min(b[0]).min(c[0]).floor() as isize;
+    let max_x = a[0].max(b[0]).max(c[0]).ceil() as isize;
+    let min_y = a[1].min(b[1]).min(c[1]).floor() as isize;
+    let max_y = a[1].max(b[1]).max(c[1]).ceil() as isize;
+
+    for y in min_y..max_y + 1 {
+        for x in min_x..max_x + 1 {
+            // The signed distance from this pixel's center to the nearest
+            // edge, which is positive inside the triangle.
+            let p = [x as f64 + 0.5, y as f64 + 0.5];
+            let distance = edge_distance(p, a, b)
+                .min(edge_distance(p, b, c))
+                .min(edge_distance(p, c, a));
+
+            // Pixels straddling an edge are partially covered.
+            let coverage = (distance + 0.5).clamp(0.0, 1.0);
+            if coverage <= 0.0 {
+                continue;
+            }
+
+            let p = if wraps {
+                wrap([x as f64, y as f64])
+            } else if x < 0 || y < 0 {
+                continue;
+            } else {
+                [x as f64, y as f64]
+            };
+            let mut color = color;
+            color.a = (color.a as f64 * coverage) as u8;
+            set_pixel(buf, p[0] as usize, p[1] as usize, color);
+        }
+    }
+}
+
+fn draw_circle(buf: &mut [u8], center: [f64; 2], radius: f64, color: Color) {
+    let r = radius.ceil() as isize;
+    for dy in -r..r + 1 {
+        for dx in -r..r + 1 {
+            let (dx, dy) = (dx as f64, dy as f64);
+            if dx * dx + dy * dy <= radius * radius {
+                let p = wrap(add(center, [dx, dy]));
+                set_pixel(buf, p[0] as usize, p[1] as usize, color);
+            }
+        }
+    }
+}
+
 #[wasm_bindgen]
-pub fn frame(frame_buffer: &mut [u8], key_down: bool) {
+pub fn frame(
+    frame_buffer: &mut [u8],
+    key_down: bool,
+    key_code: u32,
+    pointer_x: f64,
+    pointer_y: f64,
+    pointer_down: bool,
+) {
     utils::set_panic_hook();
 
     let mut state = STATE.lock().unwrap();
+    let state = &mut *state;
 
     let bg_alpha = if state.first_frame { 255 } else { 40 };
     for y in 0..WIDTH {
@@ -249,24 +621,197 @@ pub fn frame(frame_buffer: &mut [u8], key_down: bool) {
         }
     }
 
-    if key_down {
-        let n = state.flock.len();
+    // Any key that doesn't do something else adds a boid. These keys switch
+    // what is shown instead, in either mode.
+    let switches_view =
+        key_code == KEY_D || (cfg!(feature = "stats-overlay") && key_code == KEY_M);
+
+    // The statistics are only of the 2D flock, so the overlay can only be
+    // toggled in 2D, where it is drawn. In 3D, `M` does nothing.
+    #[cfg(feature = "stats-overlay")]
+    {
+        if key_down && key_code == KEY_M && state.flock3d.is_none() {
+            state.show_stats = !state.show_stats;
+        }
+    }
+
+    if key_down && key_code == KEY_D {
+        state.flock3d = match state.flock3d {
+            Some(_) => None,
+            None => Some(Flock3d::new(&state.flock)),
+        };
+    }
+
+    // The 3D mode only flies the flock around its cube; there are no
+    // obstacles, predators or pointer.
+    if let Some(flock3d) = state.flock3d.as_mut() {
+        if key_down && key_code == KEY_LEFT {
+            flock3d.turn(-0.2);
+        } else if key_down && key_code == KEY_RIGHT {
+            flock3d.turn(0.2);
+        } else if key_down && !switches_view {
+            flock3d.add(state.params.num_species);
+        }
+        flock3d.step(&mut state.grid, &state.params);
+        flock3d.draw(frame_buffer);
+        state.first_frame = false;
+        return;
+    }
+
+    for &(center, radius) in OBSTACLES {
+        draw_circle(frame_buffer, center, radius, OBSTACLE_COLOR);
+    }
+
+    // The host passes a negative position when the pointer isn't over the
+    // canvas.
+    let pointer = if pointer_x >= 0.0 && pointer_y >= 0.0 {
+        Some(Pointer {
+            position: [pointer_x, pointer_y],
+            down: pointer_down,
+        })
+    } else {
+        None
+    };
+
+    if key_down && key_code == KEY_P {
+        let n = state.predators.len();
+        state.predators.push(Predator::new(n));
+    } else if key_down && !switches_view {
+        let species = state.flock.len() % state.params.num_species;
         state.flock.push(Boid {
             position: [128.0, 128.0],
-            direction: 0.0,
-            color: COLORS[n % COLORS.len()],
+            velocity: [0.0, 1.0],
+            species,
         });
     }
 
+    state.grid.rebuild(state.flock.iter().map(|b| b.position));
+
     let new_flock: Vec<_> = state
         .flock
         .iter()
         .enumerate()
         .map(|(i, b)| {
             b.draw(frame_buffer);
-            b.next(i, &state.flock)
+            b.next(
+                i,
+                &state.flock,
+                &state.grid,
+                &state.predators,
+                pointer,
+                &state.params,
+            )
         }).collect();
 
+    let new_predators: Vec<_> = state
+        .predators
+        .iter()
+        .map(|p| {
+            p.draw(frame_buffer);
+            p.next(&state.flock, &state.grid, &state.params)
+        }).collect();
+
+    #[cfg(feature = "stats-overlay")]
+    {
+        if state.show_stats {
+            metrics::stats(&state.flock, &state.grid).draw(frame_buffer);
+        }
+    }
+
     state.flock = new_flock;
+    state.predators = new_predators;
+    for p in state.predators.iter() {
+        p.catch(&mut state.flock);
+    }
     state.first_frame = false;
 }
+
+// Set how strongly boids steer away from close neighbors, toward their
+// neighbors' heading, toward their neighbors' center, away from predators,
+// around obstacles, and toward (or away from) the pointer.
+#[wasm_bindgen]
+pub fn set_weights(
+    separation: f64,
+    alignment: f64,
+    cohesion: f64,
+    flee: f64,
+    avoid: f64,
+    pointer: f64,
+) {
+    let mut state = STATE.lock().unwrap();
+    state.params.separation = separation;
+    state.params.alignment = alignment;
+    state.params.cohesion = cohesion;
+    state.params.flee = flee;
+    state.params.avoid = avoid;
+    state.params.pointer = pointer;
+}
+
+// Set the range of speeds boids fly at, in pixels per frame, and how sharply
+// they may turn, in radians per frame.
+#[wasm_bindgen]
+pub fn set_limits(min_speed: f64, max_speed: f64, max_turn: f64) {
+    let mut state = STATE.lock().unwrap();
+    state.params.min_speed = min_speed;
+    state.params.max_speed = max_speed;
+    state.params.max_turn = max_turn;
+}
+
+// Set how many predators there are, and how fast they fly in pixels per frame.
+#[wasm_bindgen]
+pub fn set_predators(count: usize, speed: f64) {
+    let mut state = STATE.lock().unwrap();
+    let n = state.predators.len();
+    if count < n {
+        state.predators.truncate(count);
+    } else {
+        state.predators.extend((n..count).map(Predator::new));
+    }
+    state.params.predator_speed = speed;
+}
+
+// Set how many species of boids there are, and how strongly boids steer away
+// from boids of other species. Every boid is reassigned a species.
+#[wasm_bindgen]
+pub fn set_species(count: usize, avoid: f64) {
+    let mut state = STATE.lock().unwrap();
+    let count = count.max(1).min(COLORS.len());
+    state.params.num_species = count;
+    state.params.species_avoid = avoid;
+    for (i, b) in state.flock.iter_mut().enumerate() {
+        b.species = i % count;
+    }
+}
+
+// The number of separate clusters that each species' boids currently form.
+// Boids are in the same cluster when they are linked by a chain of same-species
+// neighbors. Like `flock_stats`, this is of the 2D flock.
+#[wasm_bindgen]
+pub fn species_clusters() -> Vec<u32> {
+    let mut state = STATE.lock().unwrap();
+    let state = &mut *state;
+    state.grid.rebuild(state.flock.iter().map(|b| b.position));
+    (0..state.params.num_species)
+        .map(|species| metrics::clusters(&state.flock, &state.grid, Some(species)) as u32)
+        .collect()
+}
+
+// Statistics about the 2D flock as it is now: its polarization, the average
+// distance to a boid's nearest neighbor, how many clusters it forms, and how
+// many boids there are. See `FlockStats` for what each of them means.
+//
+// These are only of the 2D flock. While the 3D mode is shown, the 2D flock
+// stays as it was when switching to 3D, and so do its statistics.
+#[wasm_bindgen]
+pub fn flock_stats() -> Vec<f64> {
+    let mut state = STATE.lock().unwrap();
+    let state = &mut *state;
+    state.grid.rebuild(state.flock.iter().map(|b| b.position));
+    let stats = metrics::stats(&state.flock, &state.grid);
+    vec![
+        stats.polarization,
+        stats.nearest_neighbor,
+        stats.clusters as f64,
+        stats.count as f64,
+    ]
+}
@@ -0,0 +1,130 @@
+// Statistics about the flock, for checking how it behaves without having to
+// look at it.
+
+use super::{length_squared, wrapped_delta, Boid};
+#[cfg(feature = "stats-overlay")]
+use super::{set_pixel, Color};
+use grid::Grid;
+
+// Statistics about the flock in a single frame.
+#[derive(Copy, Clone)]
+pub struct FlockStats {
+    // How aligned the boids' headings are, from 0 (every which way) to 1 (all
+    // the same way).
+    pub polarization: f64,
+    // The average distance from each boid to its nearest neighbor.
+    pub nearest_neighbor: f64,
+    // How many clusters the boids form, regardless of species.
+    pub clusters: u32,
+    pub count: u32,
+}
+
+pub fn stats(flock: &[Boid], grid: &Grid) -> FlockStats {
+    let mut heading = [0.0, 0.0];
+    let mut sum_nearest = 0.0;
+    for (i, b) in flock.iter().enumerate() {
+        let speed = length_squared(b.velocity).sqrt();
+        if speed > 0.0 {
+            heading[0] += b.velocity[0] / speed;
+            heading[1] += b.velocity[1] / speed;
+        }
+
+        // The grid only finds neighbors within `Boid::RADIUS`, so look at the
+        // whole flock for lonely boids.
+        let nearest = |candidates: &mut dyn Iterator<Item = usize>| {
+            candidates
+                .filter(|j| *j != i)
+                .map(|j| length_squared(wrapped_delta(b.position, flock[j].position)))
+                .fold(None, |min: Option<f64>, d| Some(min.map_or(d, |m| m.min(d))))
+        };
+        let nearest = nearest(&mut grid.near(b.position))
+            .filter(|d| *d <= Boid::RADIUS * Boid::RADIUS)
+            .or_else(|| nearest(&mut (0..flock.len())));
+        sum_nearest += nearest.map_or(0.0, |d| d.sqrt());
+    }
+
+    let n = flock.len().max(1) as f64;
+    FlockStats {
+        polarization: length_squared(heading).sqrt() / n,
+        nearest_neighbor: sum_nearest / n,
+        clusters: clusters(flock, grid, None) as u32,
+        count: flock.len() as u32,
+    }
+}
+
+#[cfg(feature = "stats-overlay")]
+impl FlockStats {
+    // Draw the statistics as bars in the top left corner: polarization, then
+    // nearest neighbor distance, then a tick per cluster, then a tick per boid.
+    pub fn draw(&self, buf: &mut [u8]) {
+        const LENGTH: usize = 64;
+        const TRACK: Color = Color {
+            r: 220,
+            g: 220,
+            b: 220,
+            a: 255,
+        };
+        const FILL: Color = Color {
+            r: 40,
+            g: 40,
+            b: 40,
+            a: 255,
+        };
+
+        let nearest = self.nearest_neighbor / Boid::RADIUS;
+        let bars = [
+            (self.polarization, 1),
+            (nearest, 1),
+            (self.clusters as f64 * 2.0 / LENGTH as f64, 2),
+            (self.count as f64 * 2.0 / LENGTH as f64, 2),
+        ];
+        for (row, &(fraction, spacing)) in bars.iter().enumerate() {
+            let filled = (fraction.clamp(0.0, 1.0) * LENGTH as f64).round() as usize;
+            for x in 0..LENGTH {
+                let color = if x < filled && x % spacing == 0 {
+                    FILL
+                } else {
+                    TRACK
+                };
+                for y in 0..3 {
+                    set_pixel(buf, 4 + x, 4 + row * 5 + y, color);
+                }
+            }
+        }
+    }
+}
+
+// Find the representative of `i`'s set, flattening the path to it as we go.
+fn find(parents: &mut [usize], mut i: usize) -> usize {
+    while parents[i] != i {
+        parents[i] = parents[parents[i]];
+        i = parents[i];
+    }
+    i
+}
+
+// The number of clusters that the boids of `species` (or of any species, if
+// it is `None`) form, where two boids are in the same cluster if they are
+// within `Boid::RADIUS` of each other, or of a chain of such boids that
+// connects them.
+pub fn clusters(flock: &[Boid], grid: &Grid, species: Option<usize>) -> usize {
+    let counted = |b: &Boid| species.is_none_or(|s| b.species == s);
+    let mut parents: Vec<usize> = (0..flock.len()).collect();
+
+    for (i, a) in flock.iter().enumerate().filter(|(_, b)| counted(b)) {
+        for j in grid.near(a.position) {
+            let b = &flock[j];
+            if j <= i || !counted(b) {
+                continue;
+            }
+            if length_squared(wrapped_delta(a.position, b.position)) <= Boid::RADIUS * Boid::RADIUS {
+                let (ri, rj) = (find(&mut parents, i), find(&mut parents, j));
+                parents[ri] = rj;
+            }
+        }
+    }
+
+    (0..flock.len())
+        .filter(|&i| counted(&flock[i]) && find(&mut parents, i) == i)
+        .count()
+}
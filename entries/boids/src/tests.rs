@@ -0,0 +1,142 @@
+use super::*;
+use std::time::Instant;
+
+// `n` boids spread over the whole world.
+fn spread_flock(n: usize) -> Vec<Boid> {
+    (0..n)
+        .map(|i| Boid {
+            position: [(i * 97 % WIDTH) as f64 + 0.5, (i * 57 % 251) as f64 + 0.25],
+            velocity: [(i as f64).cos(), (i as f64).sin()],
+            species: i % NUM_SPECIES,
+        }).collect()
+}
+
+// Step a flock the way `frame` does, with no predators or pointer.
+fn step(flock: &[Boid], grid: &mut Grid) -> Vec<Boid> {
+    grid.rebuild(flock.iter().map(|b| b.position));
+    flock
+        .iter()
+        .enumerate()
+        .map(|(i, b)| b.next(i, flock, grid, &[], None, &Params::DEFAULT))
+        .collect()
+}
+
+#[test]
+fn wrapped_delta_goes_the_short_way() {
+    assert_eq!(wrapped_delta([10.0, 20.0], [30.0, 5.0]), [20.0, -15.0]);
+    assert_eq!(wrapped_delta([250.0, 10.0], [5.0, 250.0]), [11.0, -16.0]);
+    assert_eq!(wrapped_delta([5.0, 250.0], [250.0, 10.0]), [-11.0, 16.0]);
+}
+
+#[test]
+fn flock_stays_together_across_the_seam() {
+    // A flock straddling the left and right edges, with each half veering
+    // away from the other. Only seeing each other across the edges keeps
+    // them together. On the way, it also crosses the top and bottom edges.
+    let mut flock: Vec<_> = [250.0, 254.0, 2.0, 6.0]
+        .iter()
+        .flat_map(|&x| {
+            let away = if x > 128.0 { -1.0 } else { 1.0 };
+            [136.0, 142.0, 148.0].iter().map(move |&y| Boid {
+                position: [x, y],
+                velocity: [away * 0.5, 1.0],
+                species: 0,
+            })
+        }).collect();
+    let mut grid = Grid::new();
+    for frame in 0..200 {
+        grid.rebuild(flock.iter().map(|b| b.position));
+        assert_eq!(metrics::clusters(&flock, &grid, None), 1, "frame {}", frame);
+        flock = step(&flock, &mut grid);
+    }
+}
+
+#[test]
+fn flock_aligns() {
+    // Headings a golden angle apart, so that they start out evenly spread.
+    let mut flock: Vec<_> = (0..100)
+        .map(|i| Boid {
+            position: [(i * 37 % WIDTH) as f64, (i * 23 % HEIGHT) as f64],
+            velocity: [(i as f64 * 2.4).cos(), (i as f64 * 2.4).sin()],
+            species: 0,
+        }).collect();
+    let mut grid = Grid::new();
+    grid.rebuild(flock.iter().map(|b| b.position));
+    assert!(metrics::stats(&flock, &grid).polarization < 0.2);
+
+    for _ in 0..300 {
+        flock = step(&flock, &mut grid);
+        grid.rebuild(flock.iter().map(|b| b.position));
+        if metrics::stats(&flock, &grid).polarization > 0.8 {
+            return;
+        }
+    }
+    panic!("the flock didn't align within 300 frames");
+}
+
+#[test]
+fn clusters_of_hand_placed_boids() {
+    let boid = |x, y, species| Boid {
+        position: [x, y],
+        velocity: [1.0, 0.0],
+        species,
+    };
+    let flock = [
+        // A chain whose ends are too far apart to be neighbors.
+        boid(40.0, 40.0, 0),
+        boid(65.0, 40.0, 1),
+        boid(90.0, 40.0, 0),
+        // A pair across the top and bottom edges.
+        boid(150.0, 5.0, 1),
+        boid(150.0, 250.0, 1),
+        // On their own.
+        boid(200.0, 150.0, 0),
+        boid(100.0, 200.0, 2),
+    ];
+    let mut grid = Grid::new();
+    grid.rebuild(flock.iter().map(|b| b.position));
+    assert_eq!(metrics::clusters(&flock, &grid, None), 4);
+    // Without the boid in the middle, the chain's ends are separate.
+    assert_eq!(metrics::clusters(&flock, &grid, Some(0)), 3);
+    assert_eq!(metrics::clusters(&flock, &grid, Some(1)), 2);
+    assert_eq!(metrics::clusters(&flock, &grid, Some(2)), 1);
+    assert_eq!(metrics::clusters(&flock, &grid, Some(3)), 0);
+}
+
+#[test]
+fn stats_are_of_the_2d_flock() {
+    let mut buf = vec![0; WIDTH * HEIGHT * 4];
+    STATE.lock().unwrap().flock = spread_flock(50);
+    let stats = flock_stats();
+    let clusters = species_clusters();
+
+    // Switch to 3D and let it run, pressing `M` and adding a boid on the way.
+    frame(&mut buf, true, KEY_D, -1.0, -1.0, false);
+    for key_code in [0, KEY_M, KEY_P, 0] {
+        frame(&mut buf, key_code != 0, key_code, -1.0, -1.0, false);
+    }
+    assert!(flock_stats() == stats);
+    assert!(species_clusters() == clusters);
+    #[cfg(feature = "stats-overlay")]
+    assert!(!STATE.lock().unwrap().show_stats);
+
+    frame(&mut buf, true, KEY_D, -1.0, -1.0, false);
+    assert!(STATE.lock().unwrap().flock3d.is_none());
+}
+
+// Run with `cargo test --release -- --ignored --nocapture`.
+#[test]
+#[ignore]
+fn bench_frames() {
+    let mut buf = vec![0; WIDTH * HEIGHT * 4];
+    for &n in &[100, 400, 1600, 6400] {
+        STATE.lock().unwrap().flock = spread_flock(n);
+        let frames = 20;
+        let start = Instant::now();
+        for _ in 0..frames {
+            frame(&mut buf, false, 0, -1.0, -1.0, false);
+        }
+        let ms = start.elapsed().as_secs_f64() * 1000.0 / frames as f64;
+        println!("{:5} boids: {:7.2} ms per frame", n, ms);
+    }
+}
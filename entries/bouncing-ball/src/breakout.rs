@@ -0,0 +1,323 @@
+// Breakout, played with a `Ball` and a paddle against a wall of bricks.
+//
+// The ball keeps its squash and color rotation on every impact, but has no
+// gravity or drag: it always moves at the level's speed.
+
+use super::{fill_circle, set_pixel, Ball, Color, Impact, BALL_COLORS, BG_COLOR, HEIGHT, WIDTH};
+
+const KEY_LEFT: u32 = 37;
+const KEY_RIGHT: u32 = 39;
+
+const BRICK_COLUMNS: usize = 8;
+const BRICK_ROWS: usize = 5;
+const BRICK_WIDTH: f32 = 30.0;
+const BRICK_HEIGHT: f32 = 10.0;
+const BRICK_GAP: f32 = 2.0;
+const BRICKS_TOP: f32 = 30.0;
+
+const PADDLE_WIDTH: f32 = 40.0;
+const PADDLE_HEIGHT: f32 = 6.0;
+const PADDLE_Y: f32 = 236.0;
+// How far the paddle moves for each press of an arrow key.
+const PADDLE_STEP: f32 = 16.0;
+const PADDLE_COLOR: Color = Color {
+    r: 60,
+    g: 40,
+    b: 30,
+    a: 255,
+};
+
+const BALL_RADIUS: f32 = 4.0;
+// The ball's speed on the first level, and how much faster it gets on each
+// level after that.
+const BALL_SPEED: f32 = 3.0;
+const LEVEL_SPEEDUP: f32 = 0.5;
+// How far from straight up the paddle can send the ball, as the sine of the
+// angle.
+const MAX_BOUNCE: f32 = 0.8;
+
+const LIVES: u32 = 3;
+
+// A 3x5 pixel font for the digits, one row per octal digit, top row first.
+const DIGITS: [u16; 10] = [
+    0o75557, 0o26222, 0o71747, 0o71717, 0o55711, 0o74717, 0o74757, 0o71111, 0o75757, 0o75717,
+];
+const DIGIT_SCALE: usize = 2;
+
+fn fill_rect(buf: &mut [u8], left: f32, top: f32, width: f32, height: f32, color: Color) {
+    let x0 = left.round().max(0.0) as usize;
+    let y0 = top.round().max(0.0) as usize;
+    let x1 = ((left + width).round().max(0.0) as usize).min(WIDTH);
+    let y1 = ((top + height).round().max(0.0) as usize).min(HEIGHT);
+    for y in y0..y1 {
+        for x in x0..x1 {
+            set_pixel(buf, x, y, color);
+        }
+    }
+}
+
+// Draw `n` with its last digit's right edge at `right`. Digits that would
+// start off the left edge of the frame buffer are left out.
+fn draw_number(buf: &mut [u8], mut n: u32, right: usize, top: usize, color: Color) {
+    let mut right = Some(right);
+    while let Some(left) = right.and_then(|right| right.checked_sub(3 * DIGIT_SCALE)) {
+        let glyph = DIGITS[(n % 10) as usize];
+        for row in 0..5 {
+            let bits = (glyph >> (3 * (4 - row))) & 0o7;
+            for column in 0..3 {
+                if bits & (4 >> column) != 0 {
+                    for y in 0..DIGIT_SCALE {
+                        for x in 0..DIGIT_SCALE {
+                            let px = left + column * DIGIT_SCALE + x;
+                            let py = top + row * DIGIT_SCALE + y;
+                            set_pixel(buf, px, py, color);
+                        }
+                    }
+                }
+            }
+        }
+
+        n /= 10;
+        if n == 0 {
+            break;
+        }
+        right = left.checked_sub(DIGIT_SCALE);
+    }
+}
+
+fn brick_color(row: usize) -> Color {
+    let mut color = BALL_COLORS[row % BALL_COLORS.len()];
+    for _ in 0..row / BALL_COLORS.len() {
+        color.rotate();
+    }
+    color
+}
+
+// The rectangle covered by the brick in `row` and `column`, as its left, top,
+// right and bottom edges.
+fn brick_rect(row: usize, column: usize) -> [f32; 4] {
+    let left = BRICK_GAP / 2.0 + column as f32 * (BRICK_WIDTH + BRICK_GAP);
+    let top = BRICKS_TOP + row as f32 * (BRICK_HEIGHT + BRICK_GAP);
+    [left, top, left + BRICK_WIDTH, top + BRICK_HEIGHT]
+}
+
+pub struct Breakout {
+    bricks: [[bool; BRICK_COLUMNS]; BRICK_ROWS],
+    ball: Ball,
+    // The paddle's center.
+    paddle: f32,
+    // Whether the ball is sitting on the paddle, waiting to be launched.
+    serving: bool,
+    score: u32,
+    lives: u32,
+    level: u32,
+}
+
+impl Breakout {
+    pub fn new() -> Breakout {
+        Breakout {
+            bricks: [[true; BRICK_COLUMNS]; BRICK_ROWS],
+            ball: Ball {
+                position: [0.0, 0.0],
+                velocity: [0.0, 0.0],
+                radius: BALL_RADIUS,
+                color: BALL_COLORS[0],
+                squash: 0.0,
+                squash_normal: [0.0, 1.0],
+                squash_frames: 0,
+            },
+            paddle: WIDTH as f32 / 2.0,
+            serving: true,
+            score: 0,
+            lives: LIVES,
+            level: 1,
+        }
+    }
+
+    fn speed(&self) -> f32 {
+        BALL_SPEED + LEVEL_SPEEDUP * (self.level - 1) as f32
+    }
+
+    // Move the paddle with the arrow keys or the pointer, launch the ball
+    // with any other key or a pointer press, and step the game.
+    pub fn frame(
+        &mut self,
+        buf: &mut [u8],
+        key: Option<u32>,
+        pointer: Option<[f32; 2]>,
+        pointer_down: bool,
+    ) {
+        match key {
+            Some(KEY_LEFT) => self.paddle -= PADDLE_STEP,
+            Some(KEY_RIGHT) => self.paddle += PADDLE_STEP,
+            Some(_) => self.launch(),
+            None => {}
+        }
+        if let Some(pointer) = pointer {
+            self.paddle = pointer[0];
+            if pointer_down {
+                self.launch();
+            }
+        }
+        let half = PADDLE_WIDTH / 2.0;
+        self.paddle = self.paddle.clamp(half, WIDTH as f32 - half);
+
+        if self.serving {
+            self.ball.position = [self.paddle, PADDLE_Y - BALL_RADIUS];
+        } else {
+            self.step();
+        }
+        self.draw(buf);
+    }
+
+    fn launch(&mut self) {
+        if !self.serving {
+            return;
+        }
+        if self.lives == 0 {
+            let paddle = self.paddle;
+            *self = Breakout::new();
+            self.paddle = paddle;
+        }
+        self.serving = false;
+        let speed = self.speed();
+        self.ball.velocity = [speed * 0.5, -speed * 0.75_f32.sqrt()];
+    }
+
+    fn bounce(&mut self, normal: [f32; 2]) {
+        let speed = self.speed();
+        self.ball.squash(&Impact { normal, speed });
+        self.ball.color.rotate();
+    }
+
+    fn step(&mut self) {
+        self.ball.squash_frames = self.ball.squash_frames.saturating_sub(1);
+        let r = BALL_RADIUS;
+        let [x, y] = [
+            self.ball.position[0] + self.ball.velocity[0],
+            self.ball.position[1] + self.ball.velocity[1],
+        ];
+        self.ball.position = [x, y];
+
+        // The side walls and the ceiling.
+        if x - r <= 0.0 || x + r >= WIDTH as f32 {
+            let away = if x - r <= 0.0 { 1.0 } else { -1.0 };
+            self.ball.position[0] = if away > 0.0 { r } else { WIDTH as f32 - r };
+            self.ball.velocity[0] = away * self.ball.velocity[0].abs();
+            self.bounce([away, 0.0]);
+        }
+        if y - r <= 0.0 {
+            self.ball.position[1] = r;
+            self.ball.velocity[1] = self.ball.velocity[1].abs();
+            self.bounce([0.0, 1.0]);
+        }
+
+        // The paddle sends the ball off at an angle that depends on where it
+        // was hit, so that the player can aim.
+        let [x, y] = self.ball.position;
+        let half = PADDLE_WIDTH / 2.0;
+        if self.ball.velocity[1] > 0.0
+            && y + r >= PADDLE_Y
+            && y - r <= PADDLE_Y + PADDLE_HEIGHT
+            && (x - self.paddle).abs() <= half + r
+        {
+            let speed = self.speed();
+            let offset = ((x - self.paddle) / (half + r)).clamp(-1.0, 1.0) * MAX_BOUNCE;
+            self.ball.velocity = [speed * offset, -speed * (1.0 - offset * offset).sqrt()];
+            self.ball.position[1] = PADDLE_Y - r;
+            self.bounce([0.0, -1.0]);
+        }
+
+        self.hit_brick();
+
+        if self.ball.position[1] - r > HEIGHT as f32 {
+            self.lives -= 1;
+            self.serving = true;
+        }
+    }
+
+    // Break at most one brick that the ball overlaps, and bounce the ball
+    // off of the side of the brick that it hit.
+    fn hit_brick(&mut self) {
+        let [x, y] = self.ball.position;
+        let r = BALL_RADIUS;
+        for row in 0..BRICK_ROWS {
+            for column in 0..BRICK_COLUMNS {
+                if !self.bricks[row][column] {
+                    continue;
+                }
+                let [left, top, right, bottom] = brick_rect(row, column);
+                // The distance from the ball's center to the nearest point of
+                // the brick.
+                let dx = x - x.max(left).min(right);
+                let dy = y - y.max(top).min(bottom);
+                if dx * dx + dy * dy > r * r {
+                    continue;
+                }
+
+                self.bricks[row][column] = false;
+                self.score += 10 * (BRICK_ROWS - row) as u32;
+
+                // Bounce off whichever side the ball is mostly past. A ball
+                // whose center is inside the brick is sent back the way it
+                // came vertically.
+                let normal = if dx.abs() > dy.abs() {
+                    self.ball.velocity[0] = dx.signum() * self.ball.velocity[0].abs();
+                    [dx.signum(), 0.0]
+                } else {
+                    let away = if dy != 0.0 {
+                        dy.signum()
+                    } else {
+                        -self.ball.velocity[1].signum()
+                    };
+                    self.ball.velocity[1] = away * self.ball.velocity[1].abs();
+                    [0.0, away]
+                };
+                self.bounce(normal);
+
+                if self.bricks.iter().all(|row| row.iter().all(|b| !b)) {
+                    self.level += 1;
+                    self.bricks = [[true; BRICK_COLUMNS]; BRICK_ROWS];
+                    self.serving = true;
+                }
+                return;
+            }
+        }
+    }
+
+    fn draw(&self, buf: &mut [u8]) {
+        for y in 0..HEIGHT {
+            for x in 0..WIDTH {
+                set_pixel(buf, x, y, BG_COLOR);
+            }
+        }
+
+        for (row, bricks) in self.bricks.iter().enumerate() {
+            let color = brick_color(row);
+            for (column, _) in bricks.iter().enumerate().filter(|(_, b)| **b) {
+                let [left, top, right, bottom] = brick_rect(row, column);
+                fill_rect(buf, left, top, right - left, bottom - top, color);
+            }
+        }
+
+        fill_rect(
+            buf,
+            self.paddle - PADDLE_WIDTH / 2.0,
+            PADDLE_Y,
+            PADDLE_WIDTH,
+            PADDLE_HEIGHT,
+            PADDLE_COLOR,
+        );
+
+        if self.lives > 0 || !self.serving {
+            self.ball.draw(buf);
+        }
+
+        draw_number(buf, self.score, 40, 8, PADDLE_COLOR);
+        draw_number(buf, self.level, WIDTH / 2 + 3, 8, PADDLE_COLOR);
+        for i in 0..self.lives {
+            let x = WIDTH as f32 - 8.0 - 10.0 * i as f32;
+            fill_circle(buf, [x, 13.0], BALL_RADIUS, self.ball.color);
+        }
+    }
+}
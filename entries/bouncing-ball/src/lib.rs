@@ -1,9 +1,13 @@
 extern crate cfg_if;
+extern crate lazy_static;
 extern crate wasm_bindgen;
 
+mod breakout;
 mod utils;
 
-use std::mem;
+use breakout::Breakout;
+use lazy_static::lazy_static;
+use std::sync::Mutex;
 use wasm_bindgen::prelude::*;
 
 #[derive(Copy, Clone, Debug)]
@@ -26,102 +30,536 @@ impl Color {
 const WIDTH: usize = 256;
 const HEIGHT: usize = 256;
 
+const KEY_B: u32 = 66;
+
+// Added to every ball's vertical velocity each frame.
+const GRAVITY: f32 = 0.15;
+// Every ball's velocity is scaled by this each frame, to slow it down the way
+// air would.
+const DRAG: f32 = 0.998;
+// How much of its speed a ball keeps when it bounces off a wall.
+const RESTITUTION: f32 = 0.8;
+// How much of its horizontal speed a ball keeps each frame it rolls along the
+// floor.
+const FRICTION: f32 = 0.98;
+// A ball hitting a wall slower than this stays in contact with it, rather
+// than bouncing, so that balls come to rest instead of jittering.
+const REST_SPEED: f32 = 1.0;
+
+// How much of the pointer's latest movement goes into a held ball's velocity,
+// which smooths out jittery drags.
+const DRAG_SMOOTHING: f32 = 0.5;
+// The fastest a ball can be thrown.
+const MAX_THROW: f32 = 20.0;
+
+// How long a ball stays squashed after hitting a wall, in frames, and how
+// squashed it gets at most.
+const SQUASH_FRAMES: u8 = 6;
+const MAX_SQUASH: f32 = 0.4;
+
+// How long an impact's particles live, in frames.
+const PARTICLE_LIFE: u8 = 24;
+
+// Adding balls past this many would leave them no room to move.
+const MAX_BALLS: usize = 24;
+
+// New balls cycle through these sizes and colors.
+const RADII: [f32; 5] = [10.0, 6.0, 14.0, 8.0, 12.0];
+const BALL_COLORS: [Color; 3] = [
+    Color {
+        r: 10,
+        g: 20,
+        b: 175,
+        a: 255,
+    },
+    Color {
+        r: 200,
+        g: 30,
+        b: 110,
+        a: 255,
+    },
+    Color {
+        r: 40,
+        g: 130,
+        b: 60,
+        a: 255,
+    },
+];
+
+const BG_COLOR: Color = Color {
+    r: 240,
+    g: 200,
+    b: 70,
+    a: 255,
+};
+
 fn set_pixel(buf: &mut [u8], x: usize, y: usize, color: Color) {
-    assert!(buf.len() as usize == WIDTH * HEIGHT * 4);
-    buf[x * 4 + y * WIDTH * 4 + 0] = color.r;
+    assert!(buf.len() == WIDTH * HEIGHT * 4);
+    assert!(x < WIDTH && y < HEIGHT);
+    buf[x * 4 + y * WIDTH * 4] = color.r;
     buf[x * 4 + y * WIDTH * 4 + 1] = color.g;
     buf[x * 4 + y * WIDTH * 4 + 2] = color.b;
     buf[x * 4 + y * WIDTH * 4 + 3] = color.a;
 }
 
+// Fill the pixels whose centers are inside the ellipse, skipping any part of
+// it that is outside of the frame buffer. The ellipse has radius `radii[0]`
+// along the unit vector `axis`, and `radii[1]` across it.
+fn fill_ellipse(buf: &mut [u8], center: [f32; 2], axis: [f32; 2], radii: [f32; 2], color: Color) {
+    let [ux, uy] = axis;
+    let (a2, b2) = (radii[0] * radii[0], radii[1] * radii[1]);
+
+    // A point `[dx, dy]` from the center is inside when
+    // `qa * dx^2 + qb * dx * dy + qc * dy^2 <= 1`.
+    let qa = ux * ux / a2 + uy * uy / b2;
+    let qb = 2.0 * ux * uy * (1.0 / a2 - 1.0 / b2);
+    let qc = uy * uy / a2 + ux * ux / b2;
+
+    let half_height = (a2 * uy * uy + b2 * ux * ux).sqrt();
+    let top = (center[1] - half_height).floor().max(0.0) as usize;
+    let bottom = ((center[1] + half_height).ceil().max(0.0) as usize).min(HEIGHT);
+    for y in top..bottom {
+        // Solve for the `dx` at the ellipse's edges on this row.
+        let dy = y as f32 + 0.5 - center[1];
+        let b = qb * dy;
+        let discriminant = b * b - 4.0 * qa * (qc * dy * dy - 1.0);
+        if discriminant <= 0.0 {
+            continue;
+        }
+        let root = discriminant.sqrt();
+        let start = center[0] + (-b - root) / (2.0 * qa);
+        let end = center[0] + (-b + root) / (2.0 * qa);
+
+        // Pixels from `left` up to, but not including, `right` have centers
+        // between `start` and `end`.
+        let left = (start - 0.5).ceil().max(0.0) as usize;
+        let right = ((end - 0.5).floor() + 1.0).max(0.0) as usize;
+        for x in left..right.min(WIDTH) {
+            set_pixel(buf, x, y, color);
+        }
+    }
+}
+
+fn fill_circle(buf: &mut [u8], center: [f32; 2], radius: f32, color: Color) {
+    fill_ellipse(buf, center, [1.0, 0.0], [radius, radius], color);
+}
+
+// A ball hitting a wall.
+struct Impact {
+    // Points away from the wall.
+    normal: [f32; 2],
+    speed: f32,
+}
+
 struct Ball {
-    position: [isize; 2],
-    velocity: [isize; 2],
-    radius: isize,
+    position: [f32; 2],
+    velocity: [f32; 2],
+    radius: f32,
+    color: Color,
+    // How squashed the ball is, along `squash_normal`, and for how many more
+    // frames.
+    squash: f32,
+    squash_normal: [f32; 2],
+    squash_frames: u8,
 }
 
 impl Ball {
-    fn draw(&self, buf: &mut [u8], color: Color) {
-        for dy in -self.radius..self.radius {
-            let r = self.radius as f64;
-            let width = ((r * r) - (dy as f64 * dy as f64)).sqrt().round() as isize;
-            for dx in -width..width {
-                let x = self.position[0] + dx;
-                let y = self.position[1] + dy;
-                set_pixel(buf, x as usize, y as usize, color);
-            }
+    // The `i`th ball added, spread out so that new balls don't all start in
+    // the same place or direction.
+    fn new(i: usize) -> Ball {
+        let radius = RADII[i % RADII.len()];
+        let x = 111.0 + 53.0 * i as f32;
+        let y = 37.0 + 71.0 * i as f32;
+        let span_x = WIDTH as f32 - 2.0 * radius;
+        let span_y = HEIGHT as f32 - 2.0 * radius;
+        Ball {
+            position: [radius + x % span_x, radius + y % span_y],
+            velocity: [5.0 - (i % 4) as f32 * 3.0, 4.0 - (i % 3) as f32 * 3.0],
+            radius,
+            color: BALL_COLORS[i % BALL_COLORS.len()],
+            squash: 0.0,
+            squash_normal: [0.0, 1.0],
+            squash_frames: 0,
         }
     }
 
-    fn update(&mut self) -> bool {
+    // Mass is proportional to area.
+    fn mass(&self) -> f32 {
+        self.radius * self.radius
+    }
+
+    fn draw(&self, buf: &mut [u8]) {
+        if self.squash_frames == 0 {
+            fill_circle(buf, self.position, self.radius, self.color);
+            return;
+        }
+
+        // Flatten the ball against the wall and widen it along the wall,
+        // easing back to round as the frames run out. The center moves toward
+        // the wall so that the flattened side still touches it.
+        let s = self.squash * self.squash_frames as f32 / SQUASH_FRAMES as f32;
+        let n = self.squash_normal;
+        let shift = self.radius * s;
+        fill_ellipse(
+            buf,
+            [
+                self.position[0] - n[0] * shift,
+                self.position[1] - n[1] * shift,
+            ],
+            n,
+            [self.radius * (1.0 - s), self.radius * (1.0 + s)],
+            self.color,
+        );
+    }
+
+    fn squash(&mut self, impact: &Impact) {
+        self.squash = (impact.speed / 20.0).min(MAX_SQUASH);
+        self.squash_normal = impact.normal;
+        self.squash_frames = SQUASH_FRAMES;
+    }
+
+    // Returns the hardest wall impact this frame, if the ball bounced.
+    fn update(&mut self) -> Option<Impact> {
+        self.squash_frames = self.squash_frames.saturating_sub(1);
+
+        self.velocity[1] += GRAVITY;
+        self.velocity = [self.velocity[0] * DRAG, self.velocity[1] * DRAG];
         self.position = [
             self.position[0] + self.velocity[0],
             self.position[1] + self.velocity[1],
         ];
 
-        let mut hit = false;
+        let mut hit: Option<Impact> = None;
+        let size = [WIDTH as f32, HEIGHT as f32];
+        for axis in 0..2 {
+            // Put the ball back against the wall, rather than only reversing
+            // it, since a collision with another ball can push it further out
+            // than one step of its own velocity. `away` is the direction back
+            // into the frame.
+            let away = if self.position[axis] - self.radius <= 0.0 {
+                self.position[axis] = self.radius;
+                1.0
+            } else if self.position[axis] + self.radius >= size[axis] {
+                self.position[axis] = size[axis] - self.radius;
+                -1.0
+            } else {
+                continue;
+            };
 
-        if self.position[0] - self.radius <= 0 || self.position[0] + self.radius >= WIDTH as isize {
-            self.velocity[0] = -self.velocity[0];
-            self.position[0] += self.velocity[0];
-            hit = true;
+            let speed = self.velocity[axis] * -away;
+            if speed <= 0.0 {
+                // Already moving away from the wall.
+                continue;
+            }
+            if speed < REST_SPEED {
+                self.velocity[axis] = 0.0;
+            } else {
+                self.velocity[axis] = away * speed * RESTITUTION;
+                if hit.as_ref().is_none_or(|hit| hit.speed < speed) {
+                    let mut normal = [0.0, 0.0];
+                    normal[axis] = away;
+                    hit = Some(Impact { normal, speed });
+                }
+            }
         }
 
-        if self.position[1] - self.radius <= 0 || self.position[1] + self.radius >= HEIGHT as isize
-        {
-            self.velocity[1] = -self.velocity[1];
-            self.position[1] += self.velocity[1];
-            hit = true;
+        // A ball in contact with the floor rolls to a stop.
+        if self.position[1] + self.radius >= HEIGHT as f32 {
+            self.velocity[0] *= FRICTION;
         }
 
         hit
     }
 }
 
-static mut BALL: Ball = Ball {
-    position: [111, 37],
-    velocity: [5, 3],
-    radius: 10,
-};
+// Resolve an elastic collision between `a` and `b`, if they overlap and are
+// moving toward each other. Returns whether they collided hard enough to
+// bounce.
+fn collide(a: &mut Ball, b: &mut Ball) -> bool {
+    let dx = b.position[0] - a.position[0];
+    let dy = b.position[1] - a.position[1];
+    let dist = (dx * dx + dy * dy).sqrt();
+    let min_dist = a.radius + b.radius;
+    if dist >= min_dist {
+        return false;
+    }
 
-static mut BALL_COLOR: Color = Color {
-    r: 10,
-    g: 20,
-    b: 175,
-    a: 255,
-};
+    // The collision normal, from `a` to `b`. Balls at the same position are
+    // pushed apart horizontally.
+    let (nx, ny) = if dist > 0.0 {
+        (dx / dist, dy / dist)
+    } else {
+        (1.0, 0.0)
+    };
+    let (ma, mb) = (a.mass(), b.mass());
 
-static mut BG_COLOR: Color = Color {
-    r: 240,
-    g: 200,
-    b: 70,
-    a: 255,
-};
+    // Move the balls apart so they only touch, the lighter one further.
+    let overlap = min_dist - dist;
+    let push_a = overlap * mb / (ma + mb);
+    let push_b = overlap - push_a;
+    a.position[0] -= nx * push_a;
+    a.position[1] -= ny * push_a;
+    b.position[0] += nx * push_b;
+    b.position[1] += ny * push_b;
+
+    // Only bounce if they are approaching, so that balls still overlapping
+    // after a collision don't stick together.
+    let approach = (a.velocity[0] - b.velocity[0]) * nx + (a.velocity[1] - b.velocity[1]) * ny;
+    if approach <= 0.0 {
+        return false;
+    }
+
+    // Exchange momentum along the normal, conserving kinetic energy.
+    let impulse = 2.0 * approach / (ma + mb);
+    a.velocity[0] -= impulse * mb * nx;
+    a.velocity[1] -= impulse * mb * ny;
+    b.velocity[0] += impulse * ma * nx;
+    b.velocity[1] += impulse * ma * ny;
+
+    // Balls resting on each other are pressed together by gravity every
+    // frame, which shouldn't count as a collision.
+    approach >= REST_SPEED
+}
+
+struct Particle {
+    position: [f32; 2],
+    velocity: [f32; 2],
+    color: Color,
+    life: u8,
+}
+
+impl Particle {
+    fn draw(&self, buf: &mut [u8]) {
+        let radius = 0.5 + 1.5 * self.life as f32 / PARTICLE_LIFE as f32;
+        fill_circle(buf, self.position, radius, self.color);
+    }
+
+    fn update(&mut self) {
+        self.velocity[1] += GRAVITY;
+        self.position = [
+            self.position[0] + self.velocity[0],
+            self.position[1] + self.velocity[1],
+        ];
+        self.life -= 1;
+    }
+}
+
+// A ball that the pointer has picked up.
+struct Held {
+    index: usize,
+    // From the pointer to the ball's center.
+    offset: [f32; 2],
+}
+
+struct State {
+    balls: Vec<Ball>,
+    particles: Vec<Particle>,
+    // Xorshift state for scattering particles.
+    seed: u32,
+    held: Option<Held>,
+    // While playing Breakout, this is drawn instead of `balls`.
+    breakout: Option<Breakout>,
+    // Where the pointer was last frame, if it was over the frame buffer.
+    last_pointer: Option<[f32; 2]>,
+    pointer_was_down: bool,
+}
+
+lazy_static! {
+    static ref STATE: Mutex<State> = Mutex::new(State {
+        balls: (0..3).map(Ball::new).collect(),
+        particles: Vec::new(),
+        seed: 0x2545_f491,
+        held: None,
+        breakout: None,
+        last_pointer: None,
+        pointer_was_down: false,
+    });
+}
+
+impl State {
+    // A pseudo-random number in `[0, 1)`.
+    fn random(&mut self) -> f32 {
+        self.seed ^= self.seed << 13;
+        self.seed ^= self.seed >> 17;
+        self.seed ^= self.seed << 5;
+        (self.seed >> 8) as f32 / (1 << 24) as f32
+    }
+
+    // Throw off a burst of particles from where `ball` hit the wall, spread
+    // around the wall's normal. Harder hits throw more, faster particles.
+    fn burst(&mut self, ball: usize, impact: &Impact) {
+        let Ball {
+            position,
+            radius,
+            color,
+            ..
+        } = self.balls[ball];
+        let n = impact.normal;
+        let contact = [position[0] - n[0] * radius, position[1] - n[1] * radius];
+        let count = (impact.speed * 2.0).clamp(3.0, 12.0) as usize;
+        for _ in 0..count {
+            let spread = self.random() * 2.0 - 1.0;
+            let speed = impact.speed * (0.3 + 0.5 * self.random());
+            let dir = [n[0] - n[1] * spread, n[1] + n[0] * spread];
+            let length = (dir[0] * dir[0] + dir[1] * dir[1]).sqrt();
+            self.particles.push(Particle {
+                position: contact,
+                velocity: [dir[0] * speed / length, dir[1] * speed / length],
+                color,
+                life: PARTICLE_LIFE,
+            });
+        }
+    }
+
+    // Pick up a ball when the pointer is pressed on it, carry it while the
+    // pointer is down, and throw it with the pointer's velocity on release.
+    fn handle_pointer(&mut self, pointer: Option<[f32; 2]>, down: bool) {
+        let pressed = down && !self.pointer_was_down;
+        self.pointer_was_down = down;
+        let last_pointer = self.last_pointer;
+        self.last_pointer = pointer;
+
+        let pointer = match pointer {
+            Some(pointer) if down => pointer,
+            // Releasing, or leaving the frame buffer, lets go of the ball with
+            // whatever velocity it has.
+            _ => {
+                self.held = None;
+                return;
+            }
+        };
+
+        if pressed {
+            // Balls later in the list are drawn on top, so grab the last one
+            // under the pointer.
+            self.held = self
+                .balls
+                .iter()
+                .rposition(|b| {
+                    let dx = pointer[0] - b.position[0];
+                    let dy = pointer[1] - b.position[1];
+                    dx * dx + dy * dy <= b.radius * b.radius
+                })
+                .map(|index| {
+                    let b = &mut self.balls[index];
+                    b.velocity = [0.0, 0.0];
+                    Held {
+                        index,
+                        offset: [b.position[0] - pointer[0], b.position[1] - pointer[1]],
+                    }
+                });
+        }
+
+        let held = match self.held {
+            Some(ref held) => held,
+            None => return,
+        };
+        let ball = &mut self.balls[held.index];
+
+        let moved = last_pointer.map_or([0.0, 0.0], |last| {
+            [pointer[0] - last[0], pointer[1] - last[1]]
+        });
+        let size = [WIDTH as f32, HEIGHT as f32];
+        for axis in 0..2 {
+            // Not `clamp`, whose panic for bounds that aren't constant pulls in
+            // float formatting.
+            ball.position[axis] = (pointer[axis] + held.offset[axis])
+                .max(ball.radius)
+                .min(size[axis] - ball.radius);
+
+            // The held ball keeps a velocity, even though it isn't moved by
+            // it, so that it knocks other balls around and can be thrown.
+            let velocity =
+                ball.velocity[axis] * (1.0 - DRAG_SMOOTHING) + moved[axis] * DRAG_SMOOTHING;
+            ball.velocity[axis] = velocity.clamp(-MAX_THROW, MAX_THROW);
+        }
+    }
+}
 
 #[wasm_bindgen]
-pub fn frame(frame_buffer: &mut [u8], key_down: bool) {
+pub fn frame(
+    frame_buffer: &mut [u8],
+    key_down: bool,
+    key_code: u32,
+    pointer_x: f64,
+    pointer_y: f64,
+    pointer_down: bool,
+) {
     utils::set_panic_hook();
 
+    let mut state = STATE.lock().unwrap();
+
+    let pointer = if pointer_x >= 0.0 && pointer_y >= 0.0 {
+        Some([pointer_x as f32, pointer_y as f32])
+    } else {
+        None
+    };
+
+    if key_down && key_code == KEY_B {
+        state.breakout = match state.breakout {
+            Some(_) => None,
+            None => Some(Breakout::new()),
+        };
+        state.held = None;
+    }
+    if let Some(ref mut breakout) = state.breakout {
+        let key = if key_down && key_code != KEY_B {
+            Some(key_code)
+        } else {
+            None
+        };
+        breakout.frame(frame_buffer, key, pointer, pointer_down);
+        return;
+    }
+
+    state.handle_pointer(pointer, pointer_down);
+
+    let held = state.held.as_ref().map(|held| held.index);
+
     for y in 0..WIDTH {
         for x in 0..HEIGHT {
-            set_pixel(frame_buffer, x, y, unsafe { BG_COLOR });
+            set_pixel(frame_buffer, x, y, BG_COLOR);
         }
     }
 
-    if key_down {
-        unsafe {
-            let tmp = BALL.velocity[0];
-            BALL.velocity[0] = BALL.velocity[1];
-            BALL.velocity[1] = -tmp;
+    if key_down && key_code != KEY_B && state.balls.len() < MAX_BALLS {
+        let ball = Ball::new(state.balls.len());
+        state.balls.push(ball);
+    }
+
+    for ball in &state.balls {
+        ball.draw(frame_buffer);
+    }
+    for particle in &state.particles {
+        particle.draw(frame_buffer);
+    }
+
+    let balls = &mut state.balls;
+    for i in 0..balls.len() {
+        let (left, right) = balls.split_at_mut(i + 1);
+        let a = &mut left[i];
+        for b in right {
+            if collide(a, b) {
+                a.color.rotate();
+                b.color.rotate();
+            }
         }
     }
 
-    unsafe {
-        BALL.draw(frame_buffer, unsafe { BALL_COLOR });
-        if BALL.update() {
-            BALL_COLOR.rotate();
-            BG_COLOR.rotate();
-            mem::swap(&mut BALL_COLOR, &mut BG_COLOR);
+    for i in 0..state.balls.len() {
+        if Some(i) == held {
+            continue;
         }
+        if let Some(impact) = state.balls[i].update() {
+            state.burst(i, &impact);
+            let ball = &mut state.balls[i];
+            ball.squash(&impact);
+            ball.color.rotate();
+        }
+    }
+
+    for particle in &mut state.particles {
+        particle.update();
     }
+    state.particles.retain(|p| p.life > 0);
 }
@@ -1,31 +1,272 @@
 extern crate cfg_if;
+#[cfg(feature = "dither")]
+extern crate dither;
+extern crate lazy_static;
 extern crate wasm_bindgen;
 
+mod sine;
 mod utils;
+
+#[cfg(test)]
+mod tests;
+
+use lazy_static::lazy_static;
+use sine::{cos, per_frame, radians, sin, HALF_TURN, ONE, THIRD_TURN};
+use std::sync::Mutex;
 use wasm_bindgen::prelude::*;
 
-static mut TIME: u32 = 0;
+const WIDTH: usize = 256;
+const HEIGHT: usize = 256;
+
+#[cfg(feature = "dither")]
+const KEY_D: u32 = 68;
+#[cfg(feature = "dither")]
+const KEY_P: u32 = 80;
+
+// How many frames a cross-fade from one mode to the next takes.
+const FADE_FRAMES: u32 = 48;
+
+#[derive(Copy, Clone)]
+enum Mode {
+    // The original gradient: red and green pulse over time, and blue bands
+    // scroll diagonally.
+    Gradient,
+    // Rings around a center that drifts around the screen.
+    Radial,
+    // Several sine waves at different angles and speeds, added together.
+    Interference,
+    // A grid of blobs that turns and zooms.
+    Rotating,
+}
+
+const MODES: [Mode; 4] = [
+    Mode::Gradient,
+    Mode::Radial,
+    Mode::Interference,
+    Mode::Rotating,
+];
+
+// Map a fixed-point sine to a color channel, from 0 to 255.
+fn channel(v: i32) -> u8 {
+    (((v * 128) >> 15) + 128) as u8
+}
+
+fn to_f32(v: i32) -> f32 {
+    v as f32 / ONE as f32
+}
+
+// The plasma modes map a value in `[-ONE, ONE]` to a color by running each
+// channel through a sine wave a third of a turn apart. The palette slowly
+// cycles over time, so it is worked out once per frame.
+struct Palette([[u8; 3]; 256]);
+
+impl Palette {
+    fn new(time: u32) -> Palette {
+        let offset = time.wrapping_mul(per_frame(40.0));
+        let mut colors = [[0; 3]; 256];
+        for (i, color) in colors.iter_mut().enumerate() {
+            // From -1 to 1 times `PI`, that is, half a turn either way.
+            let phase = ((i as u32) << 24)
+                .wrapping_sub(HALF_TURN)
+                .wrapping_add(offset);
+            for (k, c) in color.iter_mut().enumerate() {
+                *c = channel(sin(phase.wrapping_add(k as u32 * THIRD_TURN)));
+            }
+        }
+        Palette(colors)
+    }
+
+    fn color(&self, v: i32) -> [u8; 3] {
+        self.0[((v + ONE) >> 8).clamp(0, 255) as usize]
+    }
+}
+
+fn pixels(buf: &mut [u8]) -> impl Iterator<Item = (usize, usize, &mut [u8])> {
+    buf.chunks_mut(WIDTH * 4)
+        .enumerate()
+        .flat_map(|(y, row)| row.chunks_mut(4).enumerate().map(move |(x, p)| (x, y, p)))
+}
+
+fn put(pixel: &mut [u8], color: [u8; 3]) {
+    pixel[..3].copy_from_slice(&color);
+    pixel[3] = 255;
+}
+
+// The distance from the center of the frame buffer.
+fn center_distance(x: usize, y: usize) -> f32 {
+    let dx = x as f32 - WIDTH as f32 / 2.0;
+    let dy = y as f32 - HEIGHT as f32 / 2.0;
+    (dx * dx + dy * dy).sqrt()
+}
+
+impl Mode {
+    // Draw this mode at `time` into `buf`. Anything that doesn't depend on the
+    // pixel is worked out once up front, and anything that only depends on
+    // its row, column or diagonal once per row, column or diagonal.
+    fn draw(self, buf: &mut [u8], time: u32) {
+        match self {
+            Mode::Gradient => {
+                let r = channel(sin(time.wrapping_mul(per_frame(100.0))));
+                let g = channel(cos(time.wrapping_mul(per_frame(10.0))));
+                let mut b = [0; WIDTH + HEIGHT - 1];
+                for (s, b) in b.iter_mut().enumerate() {
+                    let phase = time.wrapping_add(s as u32).wrapping_mul(per_frame(50.0));
+                    *b = channel(cos(phase));
+                }
+                for (x, y, pixel) in pixels(buf) {
+                    put(pixel, [r, g, b[x + y]]);
+                }
+            }
+            Mode::Radial => {
+                let palette = Palette::new(time);
+                let cx = 128.0 + 64.0 * to_f32(sin(time.wrapping_mul(per_frame(90.0))));
+                let cy = 128.0 + 64.0 * to_f32(cos(time.wrapping_mul(per_frame(70.0))));
+                let offset = time.wrapping_mul(per_frame(10.0));
+                for (x, y, pixel) in pixels(buf) {
+                    let dx = x as f32 - cx;
+                    let dy = y as f32 - cy;
+                    let d = (dx * dx + dy * dy).sqrt();
+                    let v = sin(radians(d / 8.0).wrapping_sub(offset));
+                    put(pixel, palette.color(v));
+                }
+            }
+            Mode::Interference => {
+                let palette = Palette::new(time);
+                let mut columns = [0; WIDTH];
+                let column_offset = time.wrapping_mul(per_frame(20.0));
+                for (x, c) in columns.iter_mut().enumerate() {
+                    *c = sin(radians(x as f32 / 16.0).wrapping_add(column_offset));
+                }
+                let mut rows = [0; HEIGHT];
+                let row_offset = time.wrapping_mul(per_frame(30.0));
+                for (y, r) in rows.iter_mut().enumerate() {
+                    *r = sin(radians(y as f32 / 12.0).wrapping_sub(row_offset));
+                }
+                let mut diagonals = [0; WIDTH + HEIGHT - 1];
+                let diagonal_offset = time.wrapping_mul(per_frame(25.0));
+                for (s, d) in diagonals.iter_mut().enumerate() {
+                    *d = sin(radians(s as f32 / 20.0).wrapping_add(diagonal_offset));
+                }
+                let ring_offset = time.wrapping_mul(per_frame(15.0));
+                for (x, y, pixel) in pixels(buf) {
+                    let ring = radians(center_distance(x, y) / 10.0);
+                    let ring = sin(ring.wrapping_sub(ring_offset));
+                    let v = (columns[x] + rows[y] + diagonals[x + y] + ring) / 4;
+                    put(pixel, palette.color(v));
+                }
+            }
+            Mode::Rotating => {
+                let palette = Palette::new(time);
+                let angle = time.wrapping_mul(per_frame(150.0));
+                let zoom = 1.5 + to_f32(sin(time.wrapping_mul(per_frame(110.0))));
+                let scale = zoom / 16.0;
+                let sin_a = to_f32(sin(angle)) * scale;
+                let cos_a = to_f32(cos(angle)) * scale;
+                for (y, row) in buf.chunks_mut(WIDTH * 4).enumerate() {
+                    // `u` and `v` are the pixel's rotated and scaled
+                    // coordinates, which change by the same amount from one
+                    // pixel to the next along a row.
+                    let dx = -(WIDTH as f32) / 2.0;
+                    let dy = y as f32 - HEIGHT as f32 / 2.0;
+                    let mut u = dx * cos_a - dy * sin_a;
+                    let mut v = dx * sin_a + dy * cos_a;
+                    for pixel in row.chunks_mut(4) {
+                        let value = (sin(radians(u)) * sin(radians(v))) >> 15;
+                        put(pixel, palette.color(value));
+                        u += cos_a;
+                        v += sin_a;
+                    }
+                }
+            }
+        }
+    }
+}
+
+// Ways to quantize the finished frame to one of `PALETTES`, cycled through
+// with `D`. The first leaves the frame alone.
+#[cfg(feature = "dither")]
+type Dither = fn(&mut [u8], usize, &dither::Palette);
+#[cfg(feature = "dither")]
+const DITHERS: [Option<Dither>; 3] = [None, Some(dither::ordered), Some(dither::floyd_steinberg)];
+#[cfg(feature = "dither")]
+const PALETTES: [&dither::Palette; 3] = [&dither::PICO_8, &dither::GAME_BOY, &dither::CGA];
+
+struct State {
+    time: u32,
+    // Indices into `MODES`.
+    mode: usize,
+    previous: usize,
+    // How many frames are left in the cross-fade from `previous` to `mode`.
+    fade: u32,
+    // Where the previous mode is drawn during a cross-fade.
+    scratch: Vec<u8>,
+    // Indices into `DITHERS` and `PALETTES`.
+    #[cfg(feature = "dither")]
+    dither: usize,
+    #[cfg(feature = "dither")]
+    palette: usize,
+}
+
+lazy_static! {
+    static ref STATE: Mutex<State> = Mutex::new(State {
+        time: 0,
+        mode: 0,
+        previous: 0,
+        fade: 0,
+        scratch: Vec::new(),
+        #[cfg(feature = "dither")]
+        dither: 0,
+        #[cfg(feature = "dither")]
+        palette: 0,
+    });
+}
 
 #[wasm_bindgen]
-pub fn frame(frame_buffer: &mut [u8], _key_down: bool) {
+pub fn frame(frame_buffer: &mut [u8], key_down: bool, key_code: u32) {
     utils::set_panic_hook();
+    assert!(frame_buffer.len() == WIDTH * HEIGHT * 4);
+
+    let mut state = STATE.lock().unwrap();
+
+    if key_down {
+        match key_code {
+            #[cfg(feature = "dither")]
+            KEY_D => state.dither = (state.dither + 1) % DITHERS.len(),
+            #[cfg(feature = "dither")]
+            KEY_P => state.palette = (state.palette + 1) % PALETTES.len(),
+            _ => {
+                state.previous = state.mode;
+                state.mode = (state.mode + 1) % MODES.len();
+                state.fade = FADE_FRAMES;
+            }
+        }
+    }
+
+    let time = state.time;
+    state.time += 1;
+
+    MODES[state.mode].draw(frame_buffer, time);
+
+    if state.fade > 0 {
+        // How far along the cross-fade is, eased in and out, out of 256.
+        let t = 1.0 - state.fade as f32 / FADE_FRAMES as f32;
+        let mix = (t * t * (3.0 - 2.0 * t) * 256.0) as i32;
+        state.fade -= 1;
+
+        let previous = MODES[state.previous];
+        let scratch = &mut state.scratch;
+        scratch.resize(frame_buffer.len(), 0);
+        previous.draw(scratch, time);
+        for (to, from) in frame_buffer.iter_mut().zip(scratch.iter()) {
+            let (to_value, from_value) = (i32::from(*to), i32::from(*from));
+            *to = (from_value + (((to_value - from_value) * mix) >> 8)) as u8;
+        }
+    }
 
-    let time = unsafe {
-        let t = TIME;
-        TIME += 1;
-        t
-    };
-
-    for (y, row) in frame_buffer.chunks_mut(256 * 4).enumerate() {
-        for (x, chunk) in row.chunks_mut(4).enumerate() {
-            assert!(chunk.len() == 4);
-            let r = ( ( f64::from(time)  / 100.0 ).sin() * 128.0 + 128.0 ) as u8;
-            let g = ( ( f64::from(time)  / 10.0 ).cos() * 128.0 + 128.0 ) as u8;
-            let b = ( ( f64::from(time + x as u32 + y as u32)  / 50.0 ).cos() * 128.0 + 128.0 ) as u8;
-            chunk[0] = r;
-            chunk[1] = g;
-            chunk[2] = b;
-            chunk[3] = 255;
+    #[cfg(feature = "dither")]
+    {
+        if let Some(dither) = DITHERS[state.dither] {
+            dither(frame_buffer, WIDTH, PALETTES[state.palette]);
         }
     }
 }
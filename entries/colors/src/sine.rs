@@ -0,0 +1,75 @@
+// Fixed-point sines.
+//
+// Angles are phases in 2^-32nds of a turn, so they wrap around for free with
+// `u32` wrapping arithmetic, and fine enough that slow, per-frame rates of
+// change keep their speed. Sines are in 1.15 fixed point, so `ONE` is 1.0.
+
+use cfg_if::cfg_if;
+use std::f64::consts::PI;
+
+const TURN: f64 = 4294967296.0;
+pub const HALF_TURN: u32 = 1 << 31;
+pub const THIRD_TURN: u32 = (TURN / 3.0) as u32;
+pub const ONE: i32 = 1 << 15;
+
+// The phase that `time` frames of `time / divisor` radians adds up to is
+// `time * per_frame(divisor)`.
+pub const fn per_frame(divisor: f64) -> u32 {
+    (TURN / (2.0 * PI * divisor) + 0.5) as u32
+}
+
+pub fn radians(angle: f32) -> u32 {
+    (f64::from(angle) * (TURN / (2.0 * PI))) as i64 as u32
+}
+
+pub fn cos(phase: u32) -> i32 {
+    sin(phase.wrapping_add(HALF_TURN / 2))
+}
+
+cfg_if! {
+    if #[cfg(feature = "sine-table")] {
+        const SIZE: usize = 256;
+
+        // `SIZE` samples over one turn, with the first repeated at the end
+        // so that interpolating never needs to wrap.
+        static TABLE: [i16; SIZE + 1] = table();
+
+        const fn table() -> [i16; SIZE + 1] {
+            let mut table = [0; SIZE + 1];
+            let mut i = 0;
+            while i <= SIZE {
+                // Taylor series around zero, over `[-PI, PI)`.
+                let x = 2.0 * PI * (i % SIZE) as f64 / SIZE as f64;
+                let x = if x >= PI { x - 2.0 * PI } else { x };
+                let mut term = x;
+                let mut sum = x;
+                let mut k = 1;
+                while k < 12 {
+                    term *= -x * x / ((2 * k) * (2 * k + 1)) as f64;
+                    sum += term;
+                    k += 1;
+                }
+                let scaled = sum * (ONE - 1) as f64;
+                table[i] = if scaled < 0.0 { scaled - 0.5 } else { scaled + 0.5 } as i16;
+                i += 1;
+            }
+            table
+        }
+
+        // Linearly interpolate between the two nearest samples, by the next
+        // eight bits of the phase.
+        pub fn sin(phase: u32) -> i32 {
+            let shift = 32 - SIZE.trailing_zeros();
+            let i = (phase >> shift) as usize;
+            let frac = ((phase >> (shift - 8)) & 0xff) as i32;
+            let a = i32::from(TABLE[i]);
+            let b = i32::from(TABLE[i + 1]);
+            a + (((b - a) * frac) >> 8)
+        }
+    } else {
+        pub fn sin(phase: u32) -> i32 {
+            let angle = (f64::from(phase) * (2.0 * PI / TURN)) as f32;
+            (angle.sin() * (ONE - 1) as f32) as i32
+        }
+    }
+}
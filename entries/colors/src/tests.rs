@@ -0,0 +1,60 @@
+use super::*;
+use std::f64::consts::PI;
+use std::time::Instant;
+
+#[test]
+fn sine_is_within_4_levels_of_f64_sin() {
+    // Every 4097th phase covers the whole turn, and lands on every part of
+    // the table between its samples.
+    for phase in (0..=u32::MAX).step_by(4097).chain(Some(u32::MAX)) {
+        let exact = (f64::from(phase) * (2.0 * PI / 4294967296.0)).sin();
+        let exact = (exact * f64::from(ONE - 1)).round() as i32;
+        let expected = channel(exact);
+        let actual = channel(sin(phase));
+        assert!(
+            (i32::from(actual) - i32::from(expected)).abs() <= 4,
+            "phase {}: {} instead of {}",
+            phase,
+            actual,
+            expected
+        );
+
+        // A level is 256 steps of the fixed-point sine, and neighboring
+        // samples of the table are up to 804 steps apart. Check the sine
+        // itself more closely too, so that interpolating between the wrong
+        // samples doesn't go unnoticed.
+        assert!(
+            (sin(phase) - exact).abs() <= 8,
+            "phase {}: {} instead of {}",
+            phase,
+            sin(phase),
+            exact
+        );
+    }
+}
+
+// Milliseconds per frame over 400 frames, pressing a key every 100 frames so
+// that it cross-fades through every mode.
+fn time_frames(buf: &mut [u8]) -> f64 {
+    let frames = 400;
+    let start = Instant::now();
+    for i in 0..frames {
+        frame(buf, i % 100 == 99, 0);
+    }
+    start.elapsed().as_secs_f64() * 1000.0 / frames as f64
+}
+
+// Run with `cargo test --release -- --ignored --nocapture`.
+#[test]
+#[ignore]
+fn bench_frames() {
+    let mut buf = vec![0; WIDTH * HEIGHT * 4];
+    let ms = time_frames(&mut buf);
+    println!("{:>15}: {:5.2} ms per frame", "no dithering", ms);
+    #[cfg(feature = "dither")]
+    for name in ["ordered", "Floyd-Steinberg"] {
+        frame(&mut buf, true, KEY_D);
+        let ms = time_frames(&mut buf);
+        println!("{:>15}: {:5.2} ms per frame", name, ms);
+    }
+}
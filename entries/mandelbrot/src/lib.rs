@@ -1,82 +1,452 @@
 extern crate cfg_if;
-extern crate hsl;
+extern crate lazy_static;
 extern crate wasm_bindgen;
 
+mod palette;
 mod utils;
+
+#[cfg(any(test, all(target_arch = "wasm32", target_feature = "simd128")))]
+mod simd;
+
+#[cfg(test)]
+mod tests;
+
+use cfg_if::cfg_if;
+use lazy_static::lazy_static;
+use palette::Palette;
+use std::ops::{Add, Mul, Sub};
+use std::sync::Mutex;
 use wasm_bindgen::prelude::*;
 
 const WIDTH: usize = 256;
 const HEIGHT: usize = 256;
 const MAX_ITER: u8 = 255;
 
-static mut TIME: usize = 0;
-static mut MANDELBROT: Option<Vec<u8>> = None;
-static mut VIEWPORT: f64 = 0.5;
-static mut OFFSET_X: f64 = -0.29;
-static mut OFFSET_Y: f64 = -1.05;
+// How many frames the animated transition between zoom levels lasts.
+const ZOOM_FRAMES: usize = 32;
 
-fn generate_mandelbrot(viewport: f64, offset_x: f64, offset_y: f64) -> Vec<u8> {
-    let mut mandelbrot = Vec::with_capacity(WIDTH * HEIGHT);
-    for y in 0..HEIGHT {
-        for x in 0..WIDTH {
-            let x0 = (x as f64) / (WIDTH as f64) * viewport + offset_x;
-            let y0 = (y as f64) / (HEIGHT as f64) * viewport + offset_y;
-            let mut x = 0.0;
-            let mut y = 0.0;
-            let mut iter = 0;
-            while iter < MAX_ITER {
-                if x * x + y * y > 2.0 * 2.0 {
-                    break;
-                }
-                let xtemp = x * x - y * y + x0;
-                y = 2.0 * x * y + y0;
-                x = xtemp;
-                iter += 1;
+// How many rows of the next zoom level are rendered each frame while the
+// transition animates, so that it is done by the time the animation is.
+const ROWS_PER_FRAME: usize = HEIGHT.div_ceil(ZOOM_FRAMES);
+
+// A number, or a vector of numbers that are operated on lane-wise. This lets
+// the scalar and SIMD escape loops share the exact same arithmetic for each
+// fractal, so that they always agree on iteration counts.
+trait Lanes: Copy + Add<Output = Self> + Sub<Output = Self> + Mul<Output = Self> {
+    fn splat(x: f64) -> Self;
+    #[cfg(feature = "burning-ship")]
+    fn abs(self) -> Self;
+}
+
+impl Lanes for f64 {
+    #[inline]
+    fn splat(x: f64) -> f64 {
+        x
+    }
+
+    #[cfg(feature = "burning-ship")]
+    #[inline]
+    fn abs(self) -> f64 {
+        f64::abs(self)
+    }
+}
+
+// The region of the complex plane that is mapped onto the frame buffer.
+#[derive(Copy, Clone)]
+struct View {
+    viewport: f64,
+    offset_x: f64,
+    offset_y: f64,
+}
+
+impl View {
+    // The point in the complex plane that pixel `(x, y)` samples.
+    fn point(&self, x: f64, y: f64) -> (f64, f64) {
+        (
+            x / (WIDTH as f64) * self.viewport + self.offset_x,
+            y / (HEIGHT as f64) * self.viewport + self.offset_y,
+        )
+    }
+
+    // The inverse of `point`: the (fractional) pixel that samples `(x0, y0)`.
+    fn pixel(&self, x0: f64, y0: f64) -> (f64, f64) {
+        (
+            (x0 - self.offset_x) / self.viewport * WIDTH as f64,
+            (y0 - self.offset_y) / self.viewport * HEIGHT as f64,
+        )
+    }
+
+    fn zoomed_in(&self) -> View {
+        let viewport = self.viewport / 2.0;
+        View {
+            viewport,
+            offset_x: self.offset_x + viewport / 2.0,
+            offset_y: self.offset_y + viewport / 2.0,
+        }
+    }
+
+    // Interpolate between two views. The viewport is interpolated
+    // geometrically so that the zoom appears to move at a constant speed, and
+    // the center moves linearly.
+    fn lerp(&self, to: &View, t: f64) -> View {
+        let viewport = self.viewport * (to.viewport / self.viewport).powf(t);
+        let center_x = self.offset_x + self.viewport / 2.0;
+        let center_y = self.offset_y + self.viewport / 2.0;
+        let center_x = center_x + (to.offset_x + to.viewport / 2.0 - center_x) * t;
+        let center_y = center_y + (to.offset_y + to.viewport / 2.0 - center_y) * t;
+        View {
+            viewport,
+            offset_x: center_x - viewport / 2.0,
+            offset_y: center_y - viewport / 2.0,
+        }
+    }
+}
+
+// The escape-time fractal families we can draw. Each one besides the
+// Mandelbrot set is behind a cargo feature, so that its size can be measured
+// with `SIZE_REPORT=1 ./build.sh`.
+#[derive(Copy, Clone, PartialEq)]
+enum Fractal {
+    Mandelbrot,
+    #[cfg(feature = "burning-ship")]
+    BurningShip,
+    #[cfg(feature = "tricorn")]
+    Tricorn,
+    #[cfg(feature = "multibrot3")]
+    Multibrot3,
+    #[cfg(feature = "multibrot4")]
+    Multibrot4,
+}
+
+// The order that pressing `F` cycles through the fractals.
+const FRACTALS: &[Fractal] = &[
+    Fractal::Mandelbrot,
+    #[cfg(feature = "burning-ship")]
+    Fractal::BurningShip,
+    #[cfg(feature = "tricorn")]
+    Fractal::Tricorn,
+    #[cfg(feature = "multibrot3")]
+    Fractal::Multibrot3,
+    #[cfg(feature = "multibrot4")]
+    Fractal::Multibrot4,
+];
+
+const KEY_F: u32 = 70;
+const KEY_P: u32 = 80;
+
+impl Fractal {
+    // The initial view when switching to this fractal.
+    fn home(&self) -> View {
+        let (viewport, offset_x, offset_y) = match *self {
+            Fractal::Mandelbrot => (0.5, -0.29, -1.05),
+            #[cfg(feature = "burning-ship")]
+            Fractal::BurningShip => (3.5, -2.25, -1.9),
+            #[cfg(feature = "tricorn")]
+            Fractal::Tricorn => (4.0, -2.5, -2.0),
+            #[cfg(feature = "multibrot3")]
+            Fractal::Multibrot3 => (3.0, -1.5, -1.5),
+            #[cfg(feature = "multibrot4")]
+            Fractal::Multibrot4 => (3.0, -1.5, -1.5),
+        };
+        View {
+            viewport,
+            offset_x,
+            offset_y,
+        }
+    }
+
+    // One iteration of `z -> f(z) + c`, where `z = x + yi` and `c = x0 + y0i`.
+    #[inline]
+    fn step<T: Lanes>(&self, x: T, y: T, x0: T, y0: T) -> (T, T) {
+        let two = T::splat(2.0);
+        match *self {
+            Fractal::Mandelbrot => (x * x - y * y + x0, two * x * y + y0),
+            #[cfg(feature = "burning-ship")]
+            Fractal::BurningShip => (x * x - y * y + x0, two * (x * y).abs() + y0),
+            #[cfg(feature = "tricorn")]
+            Fractal::Tricorn => (x * x - y * y + x0, T::splat(-2.0) * x * y + y0),
+            #[cfg(feature = "multibrot3")]
+            Fractal::Multibrot3 => {
+                let three = T::splat(3.0);
+                (
+                    x * x * x - three * x * y * y + x0,
+                    three * x * x * y - y * y * y + y0,
+                )
+            }
+            #[cfg(feature = "multibrot4")]
+            Fractal::Multibrot4 => {
+                let (a, b) = (x * x - y * y, two * x * y);
+                (a * a - b * b + x0, two * a * b + y0)
+            }
+        }
+    }
+}
+
+// The escape times for a view, which may only be partially rendered.
+struct Image {
+    fractal: Fractal,
+    view: View,
+    iters: Vec<u8>,
+}
+
+impl Image {
+    fn new(fractal: Fractal, view: View) -> Image {
+        Image {
+            fractal,
+            view,
+            iters: Vec::with_capacity(WIDTH * HEIGHT),
+        }
+    }
+
+    fn rows(&self) -> usize {
+        self.iters.len() / WIDTH
+    }
+
+    fn is_complete(&self) -> bool {
+        self.rows() == HEIGHT
+    }
+
+    // Render up to `rows` more rows of this image. Any pixel that samples
+    // exactly the same point as a pixel in `previous` is copied rather than
+    // recomputed.
+    fn render_rows(&mut self, rows: usize, previous: Option<&Image>) {
+        let start = self.rows();
+        let end = (start + rows).min(HEIGHT);
+        for y in start..end {
+            for x in (0..WIDTH).step_by(2) {
+                let (ax, ay) = self.view.point(x as f64, y as f64);
+                let (bx, by) = self.view.point(x as f64 + 1.0, y as f64);
+                let known = |x0, y0| previous.and_then(|p| p.known(self.fractal, x0, y0));
+                let iters = escape_times(
+                    self.fractal,
+                    [ax, bx],
+                    [ay, by],
+                    [known(ax, ay), known(bx, by)],
+                );
+                self.iters.extend_from_slice(&iters);
             }
-            mandelbrot.push(iter);
         }
     }
-    mandelbrot
+
+    // Look up the rendered pixel nearest to `(x0, y0)`, if there is one.
+    fn sample(&self, x0: f64, y0: f64) -> Option<u8> {
+        let (x, y) = self.view.pixel(x0, y0);
+        let (x, y) = (x.round(), y.round());
+        if x < 0.0 || y < 0.0 || x >= WIDTH as f64 || y >= self.rows() as f64 {
+            return None;
+        }
+        Some(self.iters[x as usize + y as usize * WIDTH])
+    }
+
+    // Like `sample`, but only if some pixel sampled exactly `(x0, y0)` of the
+    // same fractal.
+    fn known(&self, fractal: Fractal, x0: f64, y0: f64) -> Option<u8> {
+        if fractal != self.fractal {
+            return None;
+        }
+        let (x, y) = self.view.pixel(x0, y0);
+        if (x - x.round()).abs() > 1e-6 || (y - y.round()).abs() > 1e-6 {
+            return None;
+        }
+        self.sample(x0, y0)
+    }
+}
+
+// An in-progress animated transition from the previous zoom level to the
+// current one.
+struct Zoom {
+    from: Image,
+    frame: usize,
+}
+
+struct State {
+    time: usize,
+    fractal: usize,
+    palette: usize,
+    colors: Palette,
+    image: Image,
+    zoom: Option<Zoom>,
+}
+
+lazy_static! {
+    static ref STATE: Mutex<State> = Mutex::new(State {
+        time: 0,
+        fractal: 0,
+        palette: 0,
+        colors: Palette::new(0),
+        image: Image::new(FRACTALS[0], FRACTALS[0].home()),
+        zoom: None,
+    });
+}
+
+// Points inside the main cardioid or the period-2 bulb are known to be in the
+// set, so there is no need to iterate them at all.
+fn in_cardioid_or_bulb(x0: f64, y0: f64) -> bool {
+    let y2 = y0 * y0;
+
+    let q = (x0 - 0.25) * (x0 - 0.25) + y2;
+    if q * (q + (x0 - 0.25)) <= 0.25 * y2 {
+        return true;
+    }
+
+    (x0 + 1.0) * (x0 + 1.0) + y2 <= 1.0 / 16.0
+}
+
+fn escape_time(fractal: Fractal, x0: f64, y0: f64) -> u8 {
+    // Match on the fractal here rather than in every iteration, so that each
+    // fractal gets its own copy of the loop with just its own `step`.
+    match fractal {
+        Fractal::Mandelbrot if in_cardioid_or_bulb(x0, y0) => MAX_ITER,
+        Fractal::Mandelbrot => escape_loop(Fractal::Mandelbrot, x0, y0),
+        #[cfg(feature = "burning-ship")]
+        Fractal::BurningShip => escape_loop(Fractal::BurningShip, x0, y0),
+        #[cfg(feature = "tricorn")]
+        Fractal::Tricorn => escape_loop(Fractal::Tricorn, x0, y0),
+        #[cfg(feature = "multibrot3")]
+        Fractal::Multibrot3 => escape_loop(Fractal::Multibrot3, x0, y0),
+        #[cfg(feature = "multibrot4")]
+        Fractal::Multibrot4 => escape_loop(Fractal::Multibrot4, x0, y0),
+    }
+}
+
+#[inline(always)]
+fn escape_loop(fractal: Fractal, x0: f64, y0: f64) -> u8 {
+    let mut x = 0.0;
+    let mut y = 0.0;
+    let mut iter = 0;
+    while iter < MAX_ITER {
+        if x * x + y * y > 2.0 * 2.0 {
+            break;
+        }
+        let next = fractal.step(x, y, x0, y0);
+        x = next.0;
+        y = next.1;
+        iter += 1;
+    }
+    iter
+}
+
+cfg_if! {
+    // Wasm has no runtime feature detection, so which loop is used is decided
+    // when building. `SIMD=1 ./build.sh` builds both, and the page loads the
+    // SIMD one only on hosts that support it.
+    if #[cfg(all(target_arch = "wasm32", target_feature = "simd128"))] {
+        // Find the escape times of two points at once, unless they are both
+        // already `known`.
+        fn escape_times(
+            fractal: Fractal,
+            x0: [f64; 2],
+            y0: [f64; 2],
+            known: [Option<u8>; 2],
+        ) -> [u8; 2] {
+            if let [Some(a), Some(b)] = known {
+                return [a, b];
+            }
+            let iters = simd::escape_time_x2(fractal, x0, y0);
+            debug_assert_eq!(
+                iters,
+                [
+                    escape_time(fractal, x0[0], y0[0]),
+                    escape_time(fractal, x0[1], y0[1]),
+                ]
+            );
+            [known[0].unwrap_or(iters[0]), known[1].unwrap_or(iters[1])]
+        }
+    } else {
+        // Find the escape times of two points one at a time, skipping any that
+        // are already `known`.
+        fn escape_times(
+            fractal: Fractal,
+            x0: [f64; 2],
+            y0: [f64; 2],
+            known: [Option<u8>; 2],
+        ) -> [u8; 2] {
+            [
+                known[0].unwrap_or_else(|| escape_time(fractal, x0[0], y0[0])),
+                known[1].unwrap_or_else(|| escape_time(fractal, x0[1], y0[1])),
+            ]
+        }
+    }
 }
 
 #[wasm_bindgen]
-pub fn frame(frame_buffer: &mut [u8], key_down: bool) {
+pub fn frame(frame_buffer: &mut [u8], key_down: bool, key_code: u32) {
     utils::set_panic_hook();
 
-    let time = unsafe {
-        let t = TIME;
-        TIME += 1;
-        t
-    };
+    let mut state = STATE.lock().unwrap();
+    let state = &mut *state;
 
-    let mut mandelbrot = unsafe { MANDELBROT.take() };
+    let time = state.time;
+    state.time += 1;
 
-    if key_down {
-        unsafe {
-            VIEWPORT /= 2.0;
-            OFFSET_X += VIEWPORT / 2.0;
-            OFFSET_Y += VIEWPORT / 2.0;
-        }
-        mandelbrot = None;
+    if key_down && key_code == KEY_P {
+        state.palette = (state.palette + 1) % palette::count();
+        state.colors = Palette::new(state.palette);
+    } else if key_down && key_code == KEY_F {
+        // Switching fractals starts over from the new fractal's home view;
+        // there is nothing to reproject.
+        state.fractal = (state.fractal + 1) % FRACTALS.len();
+        let fractal = FRACTALS[state.fractal];
+        state.image = Image::new(fractal, fractal.home());
+        state.zoom = None;
+    } else if key_down {
+        // Finish the current level first, so that all of it can be reused by
+        // the next one.
+        let previous = state.zoom.take();
+        state.image.render_rows(HEIGHT, previous.as_ref().map(|z| &z.from));
+
+        let next = Image::new(state.image.fractal, state.image.view.zoomed_in());
+        let from = std::mem::replace(&mut state.image, next);
+        state.zoom = Some(Zoom { from, frame: 0 });
     }
 
-    let mandelbrot =
-        mandelbrot.unwrap_or_else(|| unsafe { generate_mandelbrot(VIEWPORT, OFFSET_X, OFFSET_Y) });
+    let image = &mut state.image;
 
-    for (pixel, iter) in frame_buffer.chunks_mut(4).zip(mandelbrot.iter()) {
-        let color = hsl::HSL {
-            h: ((((*iter as usize * 20 - time) % 360) + 360) % 360) as f64,
-            s: 0.7,
-            l: 0.7,
-        }.to_rgb();
+    let zoom = match state.zoom.as_mut() {
+        None => {
+            image.render_rows(HEIGHT, None);
+            draw(frame_buffer, &image.iters, &state.colors, time);
+            return;
+        }
+        Some(zoom) => zoom,
+    };
 
-        pixel[0] = color.0;
-        pixel[1] = color.1;
-        pixel[2] = color.2;
-        pixel[3] = 255;
+    image.render_rows(ROWS_PER_FRAME, Some(&zoom.from));
+    zoom.frame += 1;
+
+    // Ease in and out of the zoom.
+    let t = zoom.frame as f64 / ZOOM_FRAMES as f64;
+    let t = t * t * (3.0 - 2.0 * t);
+    let view = zoom.from.view.lerp(&image.view, t);
+
+    // Scale whatever we have so far of the new level, falling back to the
+    // previous level for parts that aren't rendered yet.
+    let mut iters = Vec::with_capacity(WIDTH * HEIGHT);
+    for y in 0..HEIGHT {
+        for x in 0..WIDTH {
+            let (x0, y0) = view.point(x as f64, y as f64);
+            let iter = image
+                .sample(x0, y0)
+                .or_else(|| zoom.from.sample(x0, y0))
+                .unwrap_or(MAX_ITER);
+            iters.push(iter);
+        }
     }
+    draw(frame_buffer, &iters, &state.colors, time);
 
-    unsafe {
-        MANDELBROT = Some(mandelbrot);
+    if zoom.frame >= ZOOM_FRAMES {
+        image.render_rows(HEIGHT, Some(&zoom.from));
+        debug_assert!(image.is_complete());
+        state.zoom = None;
+    }
+}
+
+fn draw(frame_buffer: &mut [u8], iters: &[u8], colors: &Palette, time: usize) {
+    for (pixel, iter) in frame_buffer.chunks_mut(4).zip(iters.iter()) {
+        let color = colors.color(*iter, time);
+        pixel[0] = color[0];
+        pixel[1] = color[1];
+        pixel[2] = color[2];
+        pixel[3] = 255;
     }
 }
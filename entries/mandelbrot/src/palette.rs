@@ -0,0 +1,85 @@
+// Gradient palettes for coloring escape times.
+//
+// Each palette is a loop of evenly spaced color stops, which is sampled into a
+// lookup table once when the palette is chosen. Coloring a pixel is then just
+// indexing into the table, and cycling the colors over time is just shifting
+// the index.
+
+// How many entries are in a palette's lookup table.
+const SIZE: usize = 360;
+
+// How far apart in the lookup table consecutive escape times are colored.
+const STRIDE: usize = 20;
+
+// The color stops of each palette.
+const PALETTES: &[&[[u8; 3]]] = &[
+    // Rainbow: the hues of `hsl(h, 70%, 70%)`, which is linear between these.
+    &[
+        [232, 125, 125],
+        [232, 232, 125],
+        [125, 232, 125],
+        [125, 232, 232],
+        [125, 125, 232],
+        [232, 125, 232],
+    ],
+    // Fire.
+    &[
+        [20, 0, 0],
+        [180, 20, 0],
+        [250, 120, 0],
+        [255, 220, 60],
+        [255, 255, 220],
+        [250, 120, 0],
+        [180, 20, 0],
+    ],
+    // Ocean.
+    &[
+        [0, 10, 40],
+        [0, 60, 120],
+        [20, 150, 180],
+        [200, 240, 250],
+        [20, 150, 180],
+        [0, 60, 120],
+    ],
+    // Grayscale.
+    &[[0, 0, 0], [255, 255, 255]],
+    // Neon.
+    &[
+        [255, 0, 150],
+        [20, 0, 40],
+        [0, 255, 200],
+        [20, 0, 40],
+    ],
+];
+
+pub fn count() -> usize {
+    PALETTES.len()
+}
+
+pub struct Palette {
+    lut: [[u8; 3]; SIZE],
+}
+
+impl Palette {
+    pub fn new(index: usize) -> Palette {
+        let stops = PALETTES[index];
+        let mut lut = [[0; 3]; SIZE];
+        for (i, entry) in lut.iter_mut().enumerate() {
+            // Find the two stops around this entry, and linearly interpolate
+            // between them, in fixed point with `SIZE` as the denominator.
+            let pos = i * stops.len();
+            let a = stops[pos / SIZE];
+            let b = stops[(pos / SIZE + 1) % stops.len()];
+            let t = pos % SIZE;
+            for c in 0..3 {
+                entry[c] = ((a[c] as usize * (SIZE - t) + b[c] as usize * t + SIZE / 2) / SIZE) as u8;
+            }
+        }
+        Palette { lut }
+    }
+
+    pub fn color(&self, iter: u8, time: usize) -> [u8; 3] {
+        let shift = SIZE - time % SIZE;
+        self.lut[(iter as usize * STRIDE + shift) % SIZE]
+    }
+}
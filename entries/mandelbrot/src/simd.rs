@@ -0,0 +1,233 @@
+// A `simd128` version of `escape_time` that iterates two points at once, one
+// per `f64` lane.
+//
+// The lanes are only actually SIMD on wasm with `simd128` enabled. Elsewhere
+// they are plain arrays with the same behavior, so that the loop can be
+// tested natively against the scalar one.
+
+use cfg_if::cfg_if;
+use std::ops::{Add, Mul, Sub};
+
+use super::{in_cardioid_or_bulb, Fractal, Lanes, MAX_ITER};
+
+cfg_if! {
+    if #[cfg(all(target_arch = "wasm32", target_feature = "simd128"))] {
+        use std::arch::wasm32::*;
+
+        #[derive(Copy, Clone)]
+        pub struct F64x2(v128);
+
+        // A per-lane boolean.
+        #[derive(Copy, Clone)]
+        pub struct Mask(v128);
+
+        impl F64x2 {
+            #[inline]
+            fn new(a: f64, b: f64) -> F64x2 {
+                F64x2(f64x2(a, b))
+            }
+
+            #[inline]
+            fn gt(self, other: F64x2) -> Mask {
+                Mask(f64x2_gt(self.0, other.0))
+            }
+        }
+
+        impl Add for F64x2 {
+            type Output = F64x2;
+
+            #[inline]
+            fn add(self, other: F64x2) -> F64x2 {
+                F64x2(f64x2_add(self.0, other.0))
+            }
+        }
+
+        impl Sub for F64x2 {
+            type Output = F64x2;
+
+            #[inline]
+            fn sub(self, other: F64x2) -> F64x2 {
+                F64x2(f64x2_sub(self.0, other.0))
+            }
+        }
+
+        impl Mul for F64x2 {
+            type Output = F64x2;
+
+            #[inline]
+            fn mul(self, other: F64x2) -> F64x2 {
+                F64x2(f64x2_mul(self.0, other.0))
+            }
+        }
+
+        impl Lanes for F64x2 {
+            #[inline]
+            fn splat(x: f64) -> F64x2 {
+                F64x2(f64x2_splat(x))
+            }
+
+            #[cfg(feature = "burning-ship")]
+            #[inline]
+            fn abs(self) -> F64x2 {
+                F64x2(f64x2_abs(self.0))
+            }
+        }
+
+        impl Mask {
+            #[inline]
+            fn new(a: bool, b: bool) -> Mask {
+                Mask(i64x2(-(a as i64), -(b as i64)))
+            }
+
+            #[inline]
+            fn and(self, other: Mask) -> Mask {
+                Mask(v128_and(self.0, other.0))
+            }
+
+            #[inline]
+            fn and_not(self, other: Mask) -> Mask {
+                Mask(v128_andnot(self.0, other.0))
+            }
+
+            #[inline]
+            fn any(self) -> bool {
+                v128_any_true(self.0)
+            }
+
+            #[inline]
+            fn lane(self, lane: usize) -> bool {
+                i64x2_bitmask(self.0) & (1 << lane) != 0
+            }
+        }
+    } else {
+        #[derive(Copy, Clone)]
+        pub struct F64x2([f64; 2]);
+
+        // A per-lane boolean.
+        #[derive(Copy, Clone)]
+        pub struct Mask([bool; 2]);
+
+        impl F64x2 {
+            fn new(a: f64, b: f64) -> F64x2 {
+                F64x2([a, b])
+            }
+
+            fn map(self, other: F64x2, f: impl Fn(f64, f64) -> f64) -> F64x2 {
+                F64x2([f(self.0[0], other.0[0]), f(self.0[1], other.0[1])])
+            }
+
+            fn test(self, other: F64x2, f: impl Fn(f64, f64) -> bool) -> Mask {
+                Mask([f(self.0[0], other.0[0]), f(self.0[1], other.0[1])])
+            }
+
+            fn gt(self, other: F64x2) -> Mask {
+                self.test(other, |a, b| a > b)
+            }
+        }
+
+        impl Add for F64x2 {
+            type Output = F64x2;
+
+            fn add(self, other: F64x2) -> F64x2 {
+                self.map(other, |a, b| a + b)
+            }
+        }
+
+        impl Sub for F64x2 {
+            type Output = F64x2;
+
+            fn sub(self, other: F64x2) -> F64x2 {
+                self.map(other, |a, b| a - b)
+            }
+        }
+
+        impl Mul for F64x2 {
+            type Output = F64x2;
+
+            fn mul(self, other: F64x2) -> F64x2 {
+                self.map(other, |a, b| a * b)
+            }
+        }
+
+        impl Lanes for F64x2 {
+            fn splat(x: f64) -> F64x2 {
+                F64x2([x; 2])
+            }
+
+            #[cfg(feature = "burning-ship")]
+            fn abs(self) -> F64x2 {
+                F64x2([self.0[0].abs(), self.0[1].abs()])
+            }
+        }
+
+        impl Mask {
+            fn new(a: bool, b: bool) -> Mask {
+                Mask([a, b])
+            }
+
+            fn and(self, other: Mask) -> Mask {
+                Mask([self.0[0] && other.0[0], self.0[1] && other.0[1]])
+            }
+
+            fn and_not(self, other: Mask) -> Mask {
+                Mask([self.0[0] && !other.0[0], self.0[1] && !other.0[1]])
+            }
+
+            fn any(self) -> bool {
+                self.0[0] || self.0[1]
+            }
+
+            fn lane(self, lane: usize) -> bool {
+                self.0[lane]
+            }
+        }
+    }
+}
+
+// The same as `escape_time`, but for two points at once. A lane stops counting
+// once its point escapes, and the loop ends when every lane has stopped.
+pub fn escape_time_x2(fractal: Fractal, x0: [f64; 2], y0: [f64; 2]) -> [u8; 2] {
+    // As in `escape_time`, give each fractal its own copy of the loop.
+    match fractal {
+        Fractal::Mandelbrot => escape_loop_x2(Fractal::Mandelbrot, x0, y0),
+        #[cfg(feature = "burning-ship")]
+        Fractal::BurningShip => escape_loop_x2(Fractal::BurningShip, x0, y0),
+        #[cfg(feature = "tricorn")]
+        Fractal::Tricorn => escape_loop_x2(Fractal::Tricorn, x0, y0),
+        #[cfg(feature = "multibrot3")]
+        Fractal::Multibrot3 => escape_loop_x2(Fractal::Multibrot3, x0, y0),
+        #[cfg(feature = "multibrot4")]
+        Fractal::Multibrot4 => escape_loop_x2(Fractal::Multibrot4, x0, y0),
+    }
+}
+
+#[inline(always)]
+fn escape_loop_x2(fractal: Fractal, x0: [f64; 2], y0: [f64; 2]) -> [u8; 2] {
+    let mut iters = [MAX_ITER; 2];
+
+    // The lanes that are still iterating.
+    let skip = |i: usize| fractal == Fractal::Mandelbrot && in_cardioid_or_bulb(x0[i], y0[i]);
+    let mut active = Mask::new(!skip(0), !skip(1));
+
+    let cx = F64x2::new(x0[0], x0[1]);
+    let cy = F64x2::new(y0[0], y0[1]);
+    let mut x = F64x2::splat(0.0);
+    let mut y = F64x2::splat(0.0);
+
+    let mut iter = 0;
+    while iter < MAX_ITER && active.any() {
+        let escaped = (x * x + y * y).gt(F64x2::splat(2.0 * 2.0)).and(active);
+        for (lane, i) in iters.iter_mut().enumerate() {
+            if escaped.lane(lane) {
+                *i = iter;
+            }
+        }
+        active = active.and_not(escaped);
+
+        let next = fractal.step(x, y, cx, cy);
+        x = next.0;
+        y = next.1;
+        iter += 1;
+    }
+    iters
+}
@@ -0,0 +1,107 @@
+use super::*;
+use std::time::Instant;
+
+// How the whole image was rendered before the interior checks, exactly as it
+// was, to compare against.
+fn generate_mandelbrot(viewport: f64, offset_x: f64, offset_y: f64) -> Vec<u8> {
+    let mut mandelbrot = Vec::with_capacity(WIDTH * HEIGHT);
+    for y in 0..HEIGHT {
+        for x in 0..WIDTH {
+            let x0 = (x as f64) / (WIDTH as f64) * viewport + offset_x;
+            let y0 = (y as f64) / (HEIGHT as f64) * viewport + offset_y;
+            let mut x = 0.0;
+            let mut y = 0.0;
+            let mut iter = 0;
+            while iter < MAX_ITER {
+                if x * x + y * y > 2.0 * 2.0 {
+                    break;
+                }
+                let xtemp = x * x - y * y + x0;
+                y = 2.0 * x * y + y0;
+                x = xtemp;
+                iter += 1;
+            }
+            mandelbrot.push(iter);
+        }
+    }
+    mandelbrot
+}
+
+// The Mandelbrot set's home view, and after zooming in `levels` times.
+fn zoomed(levels: usize) -> View {
+    (0..levels).fold(Fractal::Mandelbrot.home(), |view, _| view.zoomed_in())
+}
+
+fn baseline_render(view: View) -> Vec<u8> {
+    generate_mandelbrot(view.viewport, view.offset_x, view.offset_y)
+}
+
+fn render(view: View) -> Vec<u8> {
+    let mut image = Image::new(Fractal::Mandelbrot, view);
+    image.render_rows(HEIGHT, None);
+    image.iters
+}
+
+// Milliseconds per call of `f`: the fastest of several, which is the least
+// disturbed by whatever else the machine is doing.
+fn time<T>(f: impl Fn() -> T) -> f64 {
+    (0..20)
+        .map(|_| {
+            let start = Instant::now();
+            std::hint::black_box(f());
+            start.elapsed().as_secs_f64() * 1000.0
+        })
+        .fold(f64::INFINITY, f64::min)
+}
+
+#[test]
+fn early_outs_match_baseline() {
+    for levels in [0, 4, 8, 12] {
+        let view = zoomed(levels);
+        assert!(
+            render(view) == baseline_render(view),
+            "zoomed in {} times",
+            levels
+        );
+    }
+}
+
+// Natively, the SIMD loop runs on plain arrays rather than `simd128` lanes,
+// which still checks all of its lane masking. To test it on real lanes, run
+// `RUSTFLAGS="-C target-feature=+simd128" cargo test --target
+// wasm32-unknown-unknown`, which runs the tests under node.
+#[test]
+fn simd_loop_matches_scalar_loop() {
+    for &fractal in FRACTALS {
+        let view = fractal.home();
+        let points: Vec<_> = (0..64)
+            .flat_map(|y| (0..64).map(move |x| view.point(x as f64 * 4.0, y as f64 * 4.0)))
+            .collect();
+        // Pair each point with one from the other end of the view, so that
+        // lanes often stop at different times.
+        for (a, b) in points.iter().zip(points.iter().rev()) {
+            assert!(
+                simd::escape_time_x2(fractal, [a.0, b.0], [a.1, b.1])
+                    == [escape_time(fractal, a.0, a.1), escape_time(fractal, b.0, b.1)]
+            );
+        }
+    }
+}
+
+// Run with `cargo test --release -- --ignored --nocapture`.
+#[test]
+#[ignore]
+fn bench_early_outs() {
+    for levels in [0, 4, 8, 12] {
+        let view = zoomed(levels);
+        let baseline = time(|| baseline_render(view));
+        let fast = time(|| render(view));
+        println!(
+            "zoomed in {:2} times: {:6.2} ms before, {:6.2} ms with early outs ({:.2}x)",
+            levels,
+            baseline,
+            fast,
+            baseline / fast
+        );
+    }
+}
@@ -0,0 +1,149 @@
+// Quantize an RGBA frame buffer to a small palette, with ordered (Bayer) or
+// error-diffusion (Floyd–Steinberg) dithering.
+//
+// Both work in place on a frame buffer laid out the way `frame` receives
+// it: rows of `width` pixels, four bytes per pixel. Alpha is left alone.
+
+#[cfg(test)]
+mod tests;
+
+pub struct Palette {
+    colors: &'static [[u8; 3]],
+    // How far ordered dithering nudges each channel, in total. About the
+    // distance between neighboring colors in the palette works well.
+    spread: i32,
+}
+
+// The Game Boy's four shades of green.
+pub const GAME_BOY: Palette = Palette::new(
+    &[[15, 56, 15], [48, 98, 48], [139, 172, 15], [155, 188, 15]],
+    96,
+);
+
+// CGA's mode 4, palette 1, high intensity.
+pub const CGA: Palette = Palette::new(
+    &[[0, 0, 0], [85, 255, 255], [255, 85, 255], [255, 255, 255]],
+    160,
+);
+
+// The PICO-8 fantasy console's sixteen colors.
+pub const PICO_8: Palette = Palette::new(
+    &[
+        [0, 0, 0],
+        [29, 43, 83],
+        [126, 37, 83],
+        [0, 135, 81],
+        [171, 82, 54],
+        [95, 87, 79],
+        [194, 195, 199],
+        [255, 241, 232],
+        [255, 0, 77],
+        [255, 163, 0],
+        [255, 236, 39],
+        [0, 228, 54],
+        [41, 173, 255],
+        [131, 118, 156],
+        [255, 119, 168],
+        [255, 204, 170],
+    ],
+    64,
+);
+
+impl Palette {
+    // A palette of `colors`, which can't be empty, and the `spread` for
+    // ordered dithering with it.
+    pub const fn new(colors: &'static [[u8; 3]], spread: i32) -> Palette {
+        assert!(!colors.is_empty(), "a palette needs at least one color");
+        Palette { colors, spread }
+    }
+
+    // The color in the palette closest to `color`.
+    pub fn nearest(&self, color: [i32; 3]) -> [u8; 3] {
+        let distance = |c: &&[u8; 3]| -> i32 {
+            (0..3)
+                .map(|i| {
+                    let d = i32::from(c[i]) - color[i];
+                    d * d
+                })
+                .sum()
+        };
+        // `new` makes sure that there is one.
+        *self.colors.iter().min_by_key(distance).unwrap()
+    }
+}
+
+// The 4x4 Bayer matrix: thresholds that are as spread out as possible from
+// their neighbors.
+const BAYER: [[i32; 4]; 4] = [[0, 8, 2, 10], [12, 4, 14, 6], [3, 11, 1, 9], [15, 7, 13, 5]];
+
+// How far ordered dithering nudges the pixel at `(x, y)`: from `-spread / 2`
+// to `spread / 2`, centered on each step.
+fn nudge(x: usize, y: usize, spread: i32) -> i32 {
+    (2 * BAYER[y % 4][x % 4] + 1) * spread / 32 - spread / 2
+}
+
+// Nudge each pixel by its threshold in the Bayer matrix before picking the
+// nearest color, so that areas between two colors come out as a regular
+// pattern of both.
+//
+// Pixels that already are a palette color are left as they are. Nudging
+// could only turn a flat area of one color into a pattern of it and its
+// neighbors, when those are closer together than `spread`.
+pub fn ordered(buf: &mut [u8], width: usize, palette: &Palette) {
+    // A frame with no columns has no pixels.
+    if width == 0 {
+        return;
+    }
+    for (i, pixel) in buf.chunks_mut(4).enumerate() {
+        if palette.colors.iter().any(|c| c[..] == pixel[..3]) {
+            continue;
+        }
+        let (x, y) = (i % width, i / width);
+        let nudge = nudge(x, y, palette.spread);
+        let color = [
+            i32::from(pixel[0]) + nudge,
+            i32::from(pixel[1]) + nudge,
+            i32::from(pixel[2]) + nudge,
+        ];
+        pixel[..3].copy_from_slice(&palette.nearest(color));
+    }
+}
+
+// Pick the nearest color for each pixel in turn, and spread the difference
+// over the neighbors that haven't been picked yet: 7/16 to the right, and
+// 3/16, 5/16 and 1/16 to the lower left, below and lower right.
+pub fn floyd_steinberg(buf: &mut [u8], width: usize, palette: &Palette) {
+    // A frame with no columns has no pixels.
+    if width == 0 {
+        return;
+    }
+
+    // The error carried into this row and the next one, with a pixel of
+    // padding at each end so that the edges don't need special cases.
+    let mut this_row = vec![[0; 3]; width + 2];
+    let mut next_row = vec![[0; 3]; width + 2];
+
+    for row in buf.chunks_mut(width * 4) {
+        for (x, pixel) in row.chunks_mut(4).enumerate() {
+            let mut color = [0; 3];
+            for c in 0..3 {
+                color[c] = (i32::from(pixel[c]) + this_row[x + 1][c] / 16).clamp(0, 255);
+            }
+            let picked = palette.nearest(color);
+            pixel[..3].copy_from_slice(&picked);
+
+            for c in 0..3 {
+                let error = color[c] - i32::from(picked[c]);
+                this_row[x + 2][c] += error * 7;
+                next_row[x][c] += error * 3;
+                next_row[x + 1][c] += error * 5;
+                next_row[x + 2][c] += error;
+            }
+        }
+
+        std::mem::swap(&mut this_row, &mut next_row);
+        for e in next_row.iter_mut() {
+            *e = [0; 3];
+        }
+    }
+}
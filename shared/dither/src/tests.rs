@@ -0,0 +1,91 @@
+use super::*;
+
+const PALETTES: [&Palette; 3] = [&GAME_BOY, &CGA, &PICO_8];
+
+const WIDTH: usize = 8;
+const HEIGHT: usize = 8;
+
+// A frame of every color in `palette` in turn, with alpha counting up so
+// that it can be told apart from the colors.
+fn palette_frame(palette: &Palette) -> Vec<u8> {
+    (0..WIDTH * HEIGHT)
+        .flat_map(|i| {
+            let c = palette.colors[i % palette.colors.len()];
+            vec![c[0], c[1], c[2], i as u8]
+        })
+        .collect()
+}
+
+fn flat_frame(gray: u8) -> Vec<u8> {
+    (0..WIDTH * HEIGHT)
+        .flat_map(|_| vec![gray, gray, gray, 255])
+        .collect()
+}
+
+#[test]
+fn ordered_keeps_palette_colors() {
+    for palette in PALETTES.iter() {
+        let frame = palette_frame(palette);
+        let mut buf = frame.clone();
+        ordered(&mut buf, WIDTH, palette);
+        assert!(buf == frame);
+    }
+}
+
+#[test]
+fn floyd_steinberg_keeps_palette_colors() {
+    for palette in PALETTES.iter() {
+        let frame = palette_frame(palette);
+        let mut buf = frame.clone();
+        floyd_steinberg(&mut buf, WIDTH, palette);
+        assert!(buf == frame);
+    }
+}
+
+#[test]
+fn nudge_is_within_half_the_spread() {
+    for spread in [0, 1, 15, 32, 64, 96, 160, 255] {
+        for y in 0..4 {
+            for x in 0..4 {
+                let nudge = nudge(x, y, spread);
+                assert!(
+                    -spread / 2 <= nudge && nudge <= spread / 2,
+                    "spread {} at ({}, {}): {}",
+                    spread,
+                    x,
+                    y,
+                    nudge
+                );
+            }
+        }
+    }
+}
+
+// Mid-gray is as far from black as from white, so Floyd–Steinberg alternates
+// between them, starting with white as 128 is a little closer to it.
+#[test]
+fn floyd_steinberg_checkers_mid_gray() {
+    let palette = Palette::new(&[[0, 0, 0], [255, 255, 255]], 255);
+    let mut buf = flat_frame(128);
+    floyd_steinberg(&mut buf, WIDTH, &palette);
+    for (i, pixel) in buf.chunks(4).enumerate() {
+        let (x, y) = (i % WIDTH, i / WIDTH);
+        let expected = if (x + y) % 2 == 0 { 255 } else { 0 };
+        assert!(
+            pixel == [expected, expected, expected, 255],
+            "at ({}, {})",
+            x,
+            y
+        );
+    }
+}
+
+#[test]
+fn zero_width_is_left_alone() {
+    for palette in PALETTES.iter() {
+        let mut buf = flat_frame(128);
+        ordered(&mut buf, 0, palette);
+        floyd_steinberg(&mut buf, 0, palette);
+        assert!(buf == flat_frame(128));
+    }
+}
@@ -1,7 +1,18 @@
 // A dependency graph that contains any wasm must all be imported
 // asynchronously. This `bootstrap.js` file does the single async import, so
 // that no one else needs to worry about it again.
-import("./XXX_MODULE.js")
+//
+// Entries with a SIMD code path also have a build that uses wasm SIMD, which
+// fails to load on hosts without it. Import that one only if this host
+// validates a tiny module that uses SIMD instructions.
+const SIMD_PROBE = new Uint8Array([
+  0, 97, 115, 109, 1, 0, 0, 0, 1, 5, 1, 96, 0, 1, 123, 3, 2, 1, 0, 10, 10, 1, 8,
+  0, 65, 0, 253, 15, 253, 98, 11,
+]);
+
+const simd = WebAssembly.validate(SIMD_PROBE);
+
+(simd ? import("./XXX_SIMD_MODULE.js") : import("./XXX_MODULE.js"))
   .then(mod => main(mod))
   .catch(e => console.error("Error:", e));
 
@@ -9,7 +20,11 @@ const HEIGHT = 256;
 const WIDTH = 256
 
 let keyDown = false;
-window.addEventListener("keydown", () => keyDown = true);
+let keyCode = 0;
+window.addEventListener("keydown", e => {
+  keyDown = true;
+  keyCode = e.keyCode;
+});
 
 let shouldStop = false;
 window.addEventListener("message", () => shouldStop = true);
@@ -18,7 +33,7 @@ async function main(mod) {
   const frameBuffer = new Uint8ClampedArray(HEIGHT * WIDTH * 4);
 
   while (true) {
-    mod.frame(frameBuffer, keyDown);
+    mod.frame(frameBuffer, keyDown, keyCode, pointerX, pointerY, pointerDown);
     render(frameBuffer);
 
     // Always check this *after* rendering at least one frame, so that the index
@@ -31,6 +46,7 @@ async function main(mod) {
     }
 
     keyDown = false;
+    keyCode = 0;
     await new Promise(resolve => requestAnimationFrame(resolve));
   }
 }
@@ -41,6 +57,29 @@ canvas.height = HEIGHT;
 
 const ctx = canvas.getContext("2d");
 
+// The pointer's position in frame buffer pixels, or -1 when it isn't over the
+// canvas.
+let pointerX = -1;
+let pointerY = -1;
+let pointerDown = false;
+
+function updatePointer(e) {
+  const rect = canvas.getBoundingClientRect();
+  pointerX = (e.clientX - rect.left) * WIDTH / rect.width;
+  pointerY = (e.clientY - rect.top) * HEIGHT / rect.height;
+}
+
+canvas.addEventListener("pointermove", updatePointer);
+canvas.addEventListener("pointerdown", e => {
+  updatePointer(e);
+  pointerDown = true;
+});
+canvas.addEventListener("pointerleave", () => {
+  pointerX = -1;
+  pointerY = -1;
+});
+window.addEventListener("pointerup", () => pointerDown = false);
+
 function render(frameBuffer) {
   let data = new ImageData(frameBuffer, WIDTH, HEIGHT);
   ctx.putImageData(data, 0, 0);
@@ -0,0 +1,30 @@
+// Runs a `cargo test --target wasm32-unknown-unknown` test binary under node,
+// so that code which only exists on wasm (like `simd128` lanes) gets tested.
+//
+// Entries use this as their cargo runner for that target. There is no
+// wasm-bindgen glue here: tests must not call into JS. The test harness's
+// output is lost, since wasm32-unknown-unknown has no stdout, so all there is
+// to report is whether every test passed. A failing test panics, which traps.
+
+const fs = require("fs");
+
+const wasm = process.argv[2];
+const compiled = new WebAssembly.Module(fs.readFileSync(wasm));
+
+const imports = {};
+for (const { module: from, name } of WebAssembly.Module.imports(compiled)) {
+  imports[from] = imports[from] || {};
+  imports[from][name] = () => {
+    throw new Error(`${wasm} called ${from}.${name}, which tests can't use`);
+  };
+}
+
+let status;
+try {
+  status = new WebAssembly.Instance(compiled, imports).exports.main(0, 0);
+} catch (e) {
+  console.error(`${wasm}: a test failed: ${e}`);
+  process.exit(101);
+}
+console.log(`${wasm}: ${status == 0 ? "all tests passed" : "tests failed"}`);
+process.exit(status);